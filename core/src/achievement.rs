@@ -0,0 +1,150 @@
+//! Badges awarded for milestones in a tree.
+//!
+//! Every achievement is a rule checked against the tree as it stands. `GTree::check_achievements`
+//! stamps the ones that newly hold; once unlocked they stay unlocked, even if the work is undone.
+
+use crate::activity::EventKind;
+use crate::skill_tree::{GNode, GTree};
+use chrono::{DateTime, Days, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// The condition an achievement is awarded for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// At least this many nodes are lit.
+    NodesLit(usize),
+    /// Task work was logged on this many days in a row.
+    Streak(u32),
+    /// This many branches, as in `GTree::branches`, are complete.
+    BranchesComplete(usize),
+    /// At least this many tasks are done.
+    TasksDone(usize),
+    /// Every node is lit.
+    TreeComplete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Achievement {
+    /// Stored in the save file, so never change one.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub rule: Rule,
+}
+
+/// Every achievement there is, in the order a gallery lists them.
+pub const ACHIEVEMENTS: &[Achievement] = &[
+    Achievement { id: "first_node", name: "First Light", description: "Light your first node", rule: Rule::NodesLit(1) },
+    Achievement { id: "tasks_10", name: "Getting Started", description: "Finish 10 tasks", rule: Rule::TasksDone(10) },
+    Achievement { id: "streak_7", name: "On a Roll", description: "Put in work 7 days in a row", rule: Rule::Streak(7) },
+    Achievement {
+        id: "branch_complete",
+        name: "Branched Out",
+        description: "Complete a whole branch",
+        rule: Rule::BranchesComplete(1),
+    },
+    Achievement { id: "tasks_100", name: "Centurion", description: "Finish 100 tasks", rule: Rule::TasksDone(100) },
+    Achievement { id: "streak_30", name: "Unstoppable", description: "Put in work 30 days in a row", rule: Rule::Streak(30) },
+    Achievement { id: "tree_complete", name: "Full Bloom", description: "Light every node in the tree", rule: Rule::TreeComplete },
+];
+
+/// Looks up a built-in achievement.
+pub fn achievement(id: &str) -> Option<&'static Achievement> {
+    ACHIEVEMENTS.iter().find(|a| a.id == id)
+}
+
+/// When an achievement was awarded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Unlock {
+    pub id: String,
+    pub at: DateTime<Utc>,
+}
+
+impl Rule {
+    pub fn holds(self, tree: &GTree) -> bool {
+        match self {
+            Rule::NodesLit(n) => tree.nodes.iter().filter(|node| node.is_lit()).count() >= n,
+            Rule::Streak(days) => tree.longest_streak() >= days,
+            Rule::BranchesComplete(n) => {
+                tree.branches().into_iter().filter(|&b| tree.subtree_progress(b) >= 1.0).count() >= n
+            }
+            Rule::TasksDone(n) => tree.tasks_done() >= n,
+            Rule::TreeComplete => !tree.nodes.is_empty() && tree.nodes.iter().all(GNode::is_lit),
+        }
+    }
+}
+
+impl GTree {
+    /// Tasks whose work is finished, over all nodes.
+    pub fn tasks_done(&self) -> usize {
+        self.nodes.iter().flat_map(|n| &n.tasks).filter(|t| t.progress() >= 1.0).count()
+    }
+
+    /// The most local days in a row on which the activity log shows task work.
+    pub fn longest_streak(&self) -> u32 {
+        let days: BTreeSet<NaiveDate> = self
+            .activity()
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e.kind,
+                    EventKind::TaskChecked { .. } | EventKind::TaskValueSet { .. } | EventKind::HabitDone { .. }
+                )
+            })
+            .map(|e| e.at.with_timezone(&Local).date_naive())
+            .collect();
+        let (mut best, mut run, mut last) = (0, 0, None);
+        for day in days {
+            run = if last.and_then(|d: NaiveDate| d.checked_add_days(Days::new(1))) == Some(day) { run + 1 } else { 1 };
+            best = best.max(run);
+            last = Some(day);
+        }
+        best
+    }
+
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.unlocked().iter().any(|u| u.id == id)
+    }
+
+    /// When `id` was unlocked, if it has been.
+    pub fn unlocked_at(&self, id: &str) -> Option<DateTime<Utc>> {
+        self.unlocked().iter().find(|u| u.id == id).map(|u| u.at)
+    }
+
+    /// Unlocks every achievement whose rule now holds and returns the ones that are new.
+    pub fn check_achievements(&mut self) -> Vec<&'static Achievement> {
+        let new: Vec<&'static Achievement> =
+            ACHIEVEMENTS.iter().filter(|a| !self.is_unlocked(a.id) && a.rule.holds(self)).collect();
+        let now = Utc::now();
+        for a in &new {
+            self.unlock(Unlock { id: a.id.to_string(), at: now });
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill_tree::{Mode, Task};
+
+    #[test]
+    fn achievements_unlock_once_and_stay_unlocked() {
+        let mut tree = GTree::new("Tree");
+        let mut node = GNode::new("Node");
+        node.tasks.push(Task::new("Task"));
+        let id = tree.add_node(node);
+        assert!(tree.check_achievements().is_empty());
+
+        tree.set_task_checked(Mode::Grind, id, 0, true).unwrap();
+        let new: Vec<_> = tree.check_achievements().iter().map(|a| a.id).collect();
+        assert_eq!(new, ["first_node", "branch_complete", "tree_complete"]);
+        assert!(tree.check_achievements().is_empty());
+
+        tree.set_task_checked(Mode::Grind, id, 0, false).unwrap();
+        assert!(tree.check_achievements().is_empty());
+        assert!(tree.is_unlocked("first_node"));
+        assert_eq!(tree.longest_streak(), 1);
+    }
+}
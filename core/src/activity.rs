@@ -0,0 +1,47 @@
+//! The append-only record of what happened to a tree and when.
+//!
+//! Events are saved with the tree. `GTree` appends to the log from its
+//! mutation methods; nothing removes or rewrites entries.
+
+use crate::skill_tree::NodeId;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Event {
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// What happened. Tasks are referred to by node and position at the time of the event.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    TaskChecked { node: NodeId, task: usize },
+    TaskUnchecked { node: NodeId, task: usize },
+    /// A new reading for a measured task.
+    TaskValueSet { node: NodeId, task: usize, value: f32 },
+    HabitDone { node: NodeId, task: usize, day: NaiveDate },
+    HabitUndone { node: NodeId, task: usize, day: NaiveDate },
+    NodeAdded { node: NodeId },
+    NodeRemoved { node: NodeId },
+    /// Anything about the node itself changed: text, layout, tasks, goals.
+    NodeEdited { node: NodeId },
+}
+
+impl EventKind {
+    /// The node the event is about.
+    pub fn node(&self) -> NodeId {
+        match *self {
+            EventKind::TaskChecked { node, .. }
+            | EventKind::TaskUnchecked { node, .. }
+            | EventKind::TaskValueSet { node, .. }
+            | EventKind::HabitDone { node, .. }
+            | EventKind::HabitUndone { node, .. }
+            | EventKind::NodeAdded { node }
+            | EventKind::NodeRemoved { node }
+            | EventKind::NodeEdited { node } => node,
+        }
+    }
+}
@@ -0,0 +1,560 @@
+//! Reversible edits and the undo/redo history built on them.
+//!
+//! Every change a front-end makes to a tree goes through a `Command`. Applying
+//! a command returns its inverse, which is what the history keeps: undoing
+//! applies the inverse, and that in turn yields the command to redo.
+
+use crate::activity::EventKind;
+use crate::graft::GraftError;
+use crate::resource::Resource;
+use crate::skill_tree::{Edge, EdgeError, EdgeKind, GNode, GTree, Mode, NodeId, RemovePolicy, TaskError, TaskKind};
+use crate::workspace::TreeId;
+use chrono::NaiveDate;
+use std::fmt;
+
+/// A piece of text on a node that `Command::EditText` can replace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Title,
+    Description,
+    Task(usize),
+    Goal(usize),
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    AddNode { node: GNode },
+    /// Adds `node` requiring `parent`, see `GTree::add_child`.
+    AddChild { parent: NodeId, node: GNode },
+    RemoveNode { node: NodeId },
+    /// Removes `node` and deals with its dependents by `policy`, see `GTree::remove_node_with`.
+    RemoveNodeWith { node: NodeId, policy: RemovePolicy },
+    /// Puts a removed node back at its old position with its edges. Produced by undoing `RemoveNode`.
+    RestoreNode { index: usize, node: GNode, edges: Vec<Edge> },
+    EditText { node: NodeId, field: TextField, text: String },
+    ToggleTask { node: NodeId, task: usize },
+    SetTaskValue { node: NodeId, task: usize, value: f32 },
+    SetHabitDone { node: NodeId, task: usize, day: NaiveDate, done: bool },
+    Move { node: NodeId, x: f32, y: f32 },
+    Connect { from: NodeId, to: NodeId, kind: EdgeKind },
+    Disconnect { from: NodeId, to: NodeId },
+    /// See `GTree::reparent`.
+    Reparent { node: NodeId, parent: Option<NodeId> },
+    MoveTask { node: NodeId, from: usize, to: usize },
+    /// Inserts a resource at `index`, or at the end when the node has fewer.
+    AddResource { node: NodeId, index: usize, resource: Resource },
+    RemoveResource { node: NodeId, index: usize },
+    /// Copies `roots` and their subtrees from `source`, see `GTree::graft`.
+    Graft { source: GTree, roots: Vec<NodeId>, under: Option<NodeId> },
+    /// Several commands undone and redone as one, applied in order.
+    Batch(Vec<Command>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    UnknownNode(NodeId),
+    UnknownField { node: NodeId, field: TextField },
+    UnknownEdge { from: NodeId, to: NodeId },
+    UnknownResource { node: NodeId, index: usize },
+    /// A workspace tree that doesn't exist.
+    UnknownTree(TreeId),
+    /// Grind mode only allows working through tasks.
+    ReadOnly,
+    Edge(EdgeError),
+    Task(TaskError),
+    Graft(GraftError),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::UnknownNode(n) => write!(f, "node {} does not exist", n),
+            CommandError::UnknownField { node, field } => write!(f, "node {} has no {:?}", node, field),
+            CommandError::UnknownEdge { from, to } => write!(f, "there is no edge {} -> {}", from, to),
+            CommandError::UnknownResource { node, index } => write!(f, "node {} has no resource {}", node, index),
+            CommandError::UnknownTree(t) => write!(f, "tree {} does not exist", t),
+            CommandError::ReadOnly => write!(f, "the tree can't be edited in grind mode"),
+            CommandError::Edge(e) => write!(f, "{}", e),
+            CommandError::Task(e) => write!(f, "{}", e),
+            CommandError::Graft(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<EdgeError> for CommandError {
+    fn from(e: EdgeError) -> Self {
+        CommandError::Edge(e)
+    }
+}
+
+impl From<TaskError> for CommandError {
+    fn from(e: TaskError) -> Self {
+        CommandError::Task(e)
+    }
+}
+
+impl From<GraftError> for CommandError {
+    fn from(e: GraftError) -> Self {
+        CommandError::Graft(e)
+    }
+}
+
+impl Command {
+    /// Working through tasks is fine in grind mode, everything else edits the tree.
+    fn is_task_work(&self) -> bool {
+        match self {
+            Command::ToggleTask { .. } | Command::SetTaskValue { .. } | Command::SetHabitDone { .. } => true,
+            Command::Batch(commands) => commands.iter().all(Command::is_task_work),
+            _ => false,
+        }
+    }
+
+    /// The nodes whose tasks the command works on.
+    pub fn task_nodes(&self) -> Vec<NodeId> {
+        match self {
+            Command::ToggleTask { node, .. } | Command::SetTaskValue { node, .. } | Command::SetHabitDone { node, .. } => {
+                vec![*node]
+            }
+            Command::Batch(commands) => commands.iter().flat_map(Command::task_nodes).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Applies the command and returns the command that reverts it.
+    /// Nothing is changed when it fails.
+    pub fn apply(&self, tree: &mut GTree, mode: Mode) -> Result<Command, CommandError> {
+        if mode == Mode::Grind && !self.is_task_work() {
+            return Err(CommandError::ReadOnly);
+        }
+        match *self {
+            Command::AddNode { ref node } => Ok(Command::RemoveNode { node: tree.add_node(node.clone()) }),
+            Command::AddChild { parent, ref node } => Ok(Command::RemoveNode { node: tree.add_child(parent, node.clone())? }),
+            Command::RemoveNode { node } => {
+                let index = tree.index_of(node).ok_or(CommandError::UnknownNode(node))?;
+                let edges = tree.edges.iter().filter(|e| e.from == node || e.to == node).copied().collect();
+                let node = tree.remove_node(node).ok_or(CommandError::UnknownNode(node))?;
+                Ok(Command::RestoreNode { index, node, edges })
+            }
+            Command::RemoveNodeWith { node, policy } => {
+                let before = tree.edges.clone();
+                let indices: Vec<(NodeId, usize)> = tree.nodes.iter().enumerate().map(|(i, n)| (n.id, i)).collect();
+                let mut removed = tree.remove_node_with(node, policy);
+                if removed.is_empty() {
+                    return Err(CommandError::UnknownNode(node));
+                }
+                // Drop the edges rehoming added or tightened and loosen those again, then put the
+                // nodes back in their old places, lowest index first, with the last restore
+                // bringing back every removed edge.
+                let mut undo: Vec<Command> = tree
+                    .edges
+                    .iter()
+                    .filter(|e| !before.contains(e))
+                    .map(|e| Command::Disconnect { from: e.from, to: e.to })
+                    .collect();
+                let index_of = |id| indices.iter().find(|&&(n, _)| n == id).map_or(0, |&(_, i)| i);
+                removed.sort_by_key(|n| index_of(n.id));
+                let touches_removed = |e: &Edge| removed.iter().any(|n| n.id == e.from || n.id == e.to);
+                let (lost, tightened): (Vec<Edge>, Vec<Edge>) =
+                    before.iter().filter(|e| !tree.edges.contains(e)).partition(|e| touches_removed(e));
+                undo.extend(tightened.iter().map(|e| Command::Connect { from: e.from, to: e.to, kind: e.kind }));
+                let last = removed.len() - 1;
+                for (i, n) in removed.into_iter().enumerate() {
+                    let edges = if i == last { lost.clone() } else { Vec::new() };
+                    undo.push(Command::RestoreNode { index: index_of(n.id), node: n, edges });
+                }
+                Ok(Command::Batch(undo))
+            }
+            Command::RestoreNode { index, ref node, ref edges } => {
+                let id = node.id;
+                tree.nodes.insert(index.min(tree.nodes.len()), node.clone());
+                tree.edges.extend(edges.iter().copied());
+                tree.record(EventKind::NodeAdded { node: id });
+                Ok(Command::RemoveNode { node: id })
+            }
+            Command::EditText { node, field, ref text } => {
+                let n = tree.node(node).ok_or(CommandError::UnknownNode(node))?;
+                let exists = match field {
+                    TextField::Title | TextField::Description => true,
+                    TextField::Task(i) => i < n.tasks.len(),
+                    TextField::Goal(i) => i < n.goals.len(),
+                };
+                if !exists {
+                    return Err(CommandError::UnknownField { node, field });
+                }
+                let old = tree.edit_node(node, |n| {
+                    let slot = match field {
+                        TextField::Title => &mut n.title,
+                        TextField::Description => &mut n.description,
+                        TextField::Task(i) => &mut n.tasks[i].content,
+                        TextField::Goal(i) => &mut n.goals[i].text,
+                    };
+                    std::mem::replace(slot, text.clone())
+                });
+                Ok(Command::EditText { node, field, text: old.unwrap_or_default() })
+            }
+            Command::ToggleTask { node, task } => {
+                let checked = task_ref(tree, node, task)?.checked;
+                tree.set_task_checked(mode, node, task, !checked)?;
+                Ok(Command::ToggleTask { node, task })
+            }
+            Command::SetTaskValue { node, task, value } => {
+                let TaskKind::Measure(m) = &task_ref(tree, node, task)?.kind else {
+                    return Err(TaskError::WrongKind { node, index: task }.into());
+                };
+                let old = m.current;
+                tree.set_task_value(mode, node, task, value)?;
+                Ok(Command::SetTaskValue { node, task, value: old })
+            }
+            Command::SetHabitDone { node, task, day, done } => {
+                let TaskKind::Habit(h) = &task_ref(tree, node, task)?.kind else {
+                    return Err(TaskError::WrongKind { node, index: task }.into());
+                };
+                let was_done = h.done_on(day);
+                tree.set_habit_done(mode, node, task, day, done)?;
+                Ok(Command::SetHabitDone { node, task, day, done: was_done })
+            }
+            Command::Move { node, x, y } => {
+                let (old_x, old_y) = tree
+                    .edit_node(node, |n| (std::mem::replace(&mut n.x, x), std::mem::replace(&mut n.y, y)))
+                    .ok_or(CommandError::UnknownNode(node))?;
+                Ok(Command::Move { node, x: old_x, y: old_y })
+            }
+            Command::Connect { from, to, kind } => {
+                tree.add_edge(from, to, kind)?;
+                Ok(Command::Disconnect { from, to })
+            }
+            Command::Disconnect { from, to } => {
+                let edge = tree.remove_edge(from, to).ok_or(CommandError::UnknownEdge { from, to })?;
+                Ok(Command::Connect { from, to, kind: edge.kind })
+            }
+            Command::Reparent { node, parent } => {
+                let old: Vec<Edge> = tree
+                    .edges
+                    .iter()
+                    .filter(|e| e.to == node && (e.kind == EdgeKind::Requires || Some(e.from) == parent))
+                    .copied()
+                    .collect();
+                tree.reparent(node, parent)?;
+                let mut undo: Vec<Command> = parent.map(|from| Command::Disconnect { from, to: node }).into_iter().collect();
+                undo.extend(old.iter().map(|e| Command::Connect { from: e.from, to: e.to, kind: e.kind }));
+                Ok(Command::Batch(undo))
+            }
+            Command::MoveTask { node, from, to } => {
+                tree.move_task(node, from, to)?;
+                Ok(Command::MoveTask { node, from: to, to: from })
+            }
+            Command::AddResource { node, index, ref resource } => {
+                let index = tree
+                    .edit_node(node, |n| {
+                        let index = index.min(n.resources.len());
+                        n.resources.insert(index, resource.clone());
+                        index
+                    })
+                    .ok_or(CommandError::UnknownNode(node))?;
+                Ok(Command::RemoveResource { node, index })
+            }
+            Command::RemoveResource { node, index } => {
+                let n = tree.node(node).ok_or(CommandError::UnknownNode(node))?;
+                if index >= n.resources.len() {
+                    return Err(CommandError::UnknownResource { node, index });
+                }
+                let resource = tree.edit_node(node, |n| n.resources.remove(index)).unwrap();
+                Ok(Command::AddResource { node, index, resource })
+            }
+            Command::Graft { ref source, ref roots, under } => {
+                let graft = tree.graft(source, roots, under)?;
+                let removals = graft.ids.iter().rev().map(|&(_, node)| Command::RemoveNode { node }).collect();
+                Ok(Command::Batch(removals))
+            }
+            Command::Batch(ref commands) => {
+                let mut inverses = Vec::with_capacity(commands.len());
+                for command in commands {
+                    match command.apply(tree, mode) {
+                        Ok(inverse) => inverses.push(inverse),
+                        Err(e) => {
+                            // Put back what already happened so a failed batch changes nothing.
+                            for inverse in inverses.iter().rev() {
+                                let _ = inverse.apply(tree, mode);
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
+                inverses.reverse();
+                Ok(Command::Batch(inverses))
+            }
+        }
+    }
+}
+
+fn task_ref(tree: &GTree, node: NodeId, task: usize) -> Result<&crate::skill_tree::Task, CommandError> {
+    let n = tree.node(node).ok_or(CommandError::UnknownNode(node))?;
+    n.tasks
+        .get(task)
+        .ok_or(CommandError::Task(TaskError::UnknownTask { node, index: task }))
+}
+
+/// Undo and redo stacks of inverse commands.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies `command` and makes it undoable. A new edit drops everything that could be redone.
+    pub fn execute(&mut self, tree: &mut GTree, mode: Mode, command: Command) -> Result<(), CommandError> {
+        let inverse = command.apply(tree, mode)?;
+        self.undo.push(inverse);
+        self.redo.clear();
+        Ok(())
+    }
+
+    /// Reverts the last edit. Returns `Ok(false)` if there is nothing to undo.
+    pub fn undo(&mut self, tree: &mut GTree, mode: Mode) -> Result<bool, CommandError> {
+        Self::step(&mut self.undo, &mut self.redo, tree, mode)
+    }
+
+    /// Re-applies the last undone edit. Returns `Ok(false)` if there is nothing to redo.
+    pub fn redo(&mut self, tree: &mut GTree, mode: Mode) -> Result<bool, CommandError> {
+        Self::step(&mut self.redo, &mut self.undo, tree, mode)
+    }
+
+    /// What `undo` would apply next.
+    pub fn next_undo(&self) -> Option<&Command> {
+        self.undo.last()
+    }
+
+    /// What `redo` would apply next.
+    pub fn next_redo(&self) -> Option<&Command> {
+        self.redo.last()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn step(from: &mut Vec<Command>, to: &mut Vec<Command>, tree: &mut GTree, mode: Mode) -> Result<bool, CommandError> {
+        let Some(command) = from.last() else {
+            return Ok(false);
+        };
+        // Only take the command off its stack once it applied, so a refused undo can be retried.
+        let inverse = command.apply(tree, mode)?;
+        from.pop();
+        to.push(inverse);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    /// a -> b -> c, plus a -> d; b has two tasks.
+    fn sample() -> (GTree, [NodeId; 4]) {
+        let mut tree = GTree::new("sample");
+        let a = tree.add_node(GNode::new("a"));
+        let mut b = GNode::new("b");
+        b.tasks.push(crate::skill_tree::Task::new("first"));
+        b.tasks.push(crate::skill_tree::Task::new("second"));
+        let b = tree.add_child(a, b).unwrap();
+        let c = tree.add_child(b, GNode::new("c")).unwrap();
+        let d = tree.add_child(a, GNode::new("d")).unwrap();
+        (tree, [a, b, c, d])
+    }
+
+    /// The nodes in order and the edges in any order; the activity log only ever grows.
+    fn snapshot(tree: &GTree) -> (Value, Vec<(u32, u32, EdgeKind)>) {
+        let mut edges: Vec<_> = tree.edges.iter().map(|e| (e.from.0, e.to.0, e.kind)).collect();
+        edges.sort_by_key(|&(from, to, _)| (from, to));
+        (serde_json::to_value(&tree.nodes).unwrap(), edges)
+    }
+
+    /// Applies `command`, undoes it and redoes it, checking the tree each time.
+    fn round_trip(tree: &mut GTree, mode: Mode, command: Command) {
+        let before = snapshot(tree);
+        let undo = command.apply(tree, mode).unwrap();
+        let after = snapshot(tree);
+        assert_ne!(before, after, "{:?} changed nothing", command);
+        let redo = undo.apply(tree, mode).unwrap();
+        assert_eq!(snapshot(tree), before, "undoing {:?}", command);
+        redo.apply(tree, mode).unwrap();
+        assert_eq!(snapshot(tree), after, "redoing {:?}", command);
+    }
+
+    #[test]
+    fn add_child_round_trips() {
+        let (mut tree, [_, _, c, _]) = sample();
+        round_trip(&mut tree, Mode::Edit, Command::AddChild { parent: c, node: GNode::new("e") });
+    }
+
+    #[test]
+    fn remove_node_with_round_trips_every_policy() {
+        for policy in [RemovePolicy::Detach, RemovePolicy::Rehome, RemovePolicy::Cascade] {
+            let (mut tree, [_, b, _, _]) = sample();
+            round_trip(&mut tree, Mode::Edit, Command::RemoveNodeWith { node: b, policy });
+        }
+        // Rehoming tightens a -> c from Recommends to Requires; undoing has to loosen it again.
+        let (mut tree, [a, b, c, _]) = sample();
+        tree.add_edge(a, c, EdgeKind::Recommends).unwrap();
+        round_trip(&mut tree, Mode::Edit, Command::RemoveNodeWith { node: b, policy: RemovePolicy::Rehome });
+    }
+
+    #[test]
+    fn reparent_round_trips() {
+        let (mut tree, [_, b, c, d]) = sample();
+        tree.add_edge(d, c, EdgeKind::Recommends).unwrap();
+        round_trip(&mut tree, Mode::Edit, Command::Reparent { node: c, parent: Some(d) });
+        round_trip(&mut tree, Mode::Edit, Command::Reparent { node: b, parent: None });
+    }
+
+    #[test]
+    fn move_task_round_trips() {
+        let (mut tree, [_, b, _, _]) = sample();
+        round_trip(&mut tree, Mode::Edit, Command::MoveTask { node: b, from: 0, to: 1 });
+    }
+
+    #[test]
+    fn node_commands_round_trip() {
+        let (mut tree, [a, b, _, d]) = sample();
+        round_trip(&mut tree, Mode::Edit, Command::AddNode { node: GNode::new("e") });
+        round_trip(&mut tree, Mode::Edit, Command::Move { node: d, x: 40.0, y: -12.5 });
+        round_trip(&mut tree, Mode::Edit, Command::Connect { from: d, to: b, kind: EdgeKind::Recommends });
+        round_trip(&mut tree, Mode::Edit, Command::Disconnect { from: a, to: d });
+        round_trip(&mut tree, Mode::Edit, Command::RemoveNode { node: b });
+    }
+
+    #[test]
+    fn edit_text_round_trips_every_field() {
+        let (mut tree, [_, b, _, _]) = sample();
+        tree.edit_node(b, |n| n.goals.push(crate::skill_tree::Goal::new("goal")));
+        for field in [TextField::Title, TextField::Description, TextField::Task(1), TextField::Goal(0)] {
+            round_trip(&mut tree, Mode::Edit, Command::EditText { node: b, field, text: "new".to_string() });
+        }
+        let missing = Command::EditText { node: b, field: TextField::Goal(1), text: String::new() };
+        assert_eq!(
+            missing.apply(&mut tree, Mode::Edit).unwrap_err(),
+            CommandError::UnknownField { node: b, field: TextField::Goal(1) }
+        );
+    }
+
+    #[test]
+    fn task_work_round_trips() {
+        let (mut tree, [_, b, _, _]) = sample();
+        let measure = crate::skill_tree::Measure {
+            unit: "kg".to_string(),
+            start: 0.0,
+            target: 10.0,
+            current: 2.0,
+            direction: Default::default(),
+        };
+        tree.edit_node(b, |n| {
+            n.tasks[0].kind = TaskKind::Habit(crate::habit::Habit::new(crate::habit::Schedule::Daily));
+            n.tasks.push(crate::skill_tree::Task::new("third"));
+            n.tasks[2].kind = TaskKind::Measure(measure);
+        });
+        let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        round_trip(&mut tree, Mode::Edit, Command::ToggleTask { node: b, task: 1 });
+        round_trip(&mut tree, Mode::Edit, Command::SetTaskValue { node: b, task: 2, value: 7.5 });
+        round_trip(&mut tree, Mode::Edit, Command::SetHabitDone { node: b, task: 0, day, done: true });
+
+        let wrong = Command::SetTaskValue { node: b, task: 1, value: 1.0 };
+        assert_eq!(
+            wrong.apply(&mut tree, Mode::Edit).unwrap_err(),
+            CommandError::Task(TaskError::WrongKind { node: b, index: 1 })
+        );
+    }
+
+    #[test]
+    fn resource_commands_round_trip() {
+        let (mut tree, [_, b, _, _]) = sample();
+        let resource = Resource::url("Docs", crate::resource::ResourceKind::Docs, "https://example.com");
+        round_trip(&mut tree, Mode::Edit, Command::AddResource { node: b, index: 5, resource });
+        round_trip(&mut tree, Mode::Edit, Command::RemoveResource { node: b, index: 0 });
+        let missing = Command::RemoveResource { node: b, index: 1 };
+        assert_eq!(
+            missing.apply(&mut tree, Mode::Edit).unwrap_err(),
+            CommandError::UnknownResource { node: b, index: 1 }
+        );
+    }
+
+    #[test]
+    fn graft_round_trips() {
+        let (mut tree, [_, _, c, _]) = sample();
+        let (source, [a, b, _, _]) = sample();
+        round_trip(&mut tree, Mode::Edit, Command::Graft { source: source.clone(), roots: vec![b], under: Some(c) });
+        round_trip(&mut tree, Mode::Edit, Command::Graft { source, roots: vec![a], under: None });
+    }
+
+    #[test]
+    fn batch_round_trips_and_rolls_back_on_failure() {
+        let (mut tree, [a, b, c, d]) = sample();
+        let batch = Command::Batch(vec![
+            Command::Move { node: a, x: 1.0, y: 2.0 },
+            Command::Disconnect { from: b, to: c },
+            Command::EditText { node: d, field: TextField::Title, text: "D".to_string() },
+        ]);
+        round_trip(&mut tree, Mode::Edit, batch);
+
+        let before = snapshot(&tree);
+        let failing = Command::Batch(vec![
+            Command::Move { node: a, x: 9.0, y: 9.0 },
+            Command::Disconnect { from: a, to: d },
+            Command::Disconnect { from: d, to: a },
+        ]);
+        assert_eq!(failing.apply(&mut tree, Mode::Edit).unwrap_err(), CommandError::UnknownEdge { from: d, to: a });
+        assert_eq!(snapshot(&tree), before);
+    }
+
+    #[test]
+    fn grind_mode_only_allows_task_work() {
+        let (mut tree, [a, b, _, _]) = sample();
+        let before = snapshot(&tree);
+        let edits = [
+            Command::Move { node: a, x: 1.0, y: 1.0 },
+            Command::AddNode { node: GNode::new("e") },
+            Command::Batch(vec![Command::ToggleTask { node: b, task: 0 }, Command::RemoveNode { node: a }]),
+        ];
+        for command in edits {
+            assert_eq!(command.apply(&mut tree, Mode::Grind).unwrap_err(), CommandError::ReadOnly);
+        }
+        assert_eq!(snapshot(&tree), before);
+
+        tree.add_node(GNode::new("e"));
+        let e = tree.nodes.last().unwrap().id;
+        tree.edit_node(e, |n| n.tasks.push(crate::skill_tree::Task::new("task")));
+        round_trip(&mut tree, Mode::Grind, Command::ToggleTask { node: e, task: 0 });
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let (mut tree, [a, _, _, d]) = sample();
+        let mut history = History::new();
+        history.execute(&mut tree, Mode::Edit, Command::Move { node: a, x: 1.0, y: 1.0 }).unwrap();
+        assert!(history.undo(&mut tree, Mode::Edit).unwrap());
+        assert!(history.can_redo());
+
+        history.execute(&mut tree, Mode::Edit, Command::Move { node: d, x: 2.0, y: 2.0 }).unwrap();
+        assert!(!history.can_redo());
+        assert!(!history.redo(&mut tree, Mode::Edit).unwrap());
+        assert!(history.undo(&mut tree, Mode::Edit).unwrap());
+        assert!(!history.undo(&mut tree, Mode::Edit).unwrap());
+        assert!(!history.can_undo());
+    }
+}
@@ -0,0 +1,68 @@
+//! Due dates on tasks and target dates on nodes.
+
+use crate::skill_tree::{GTree, NodeId};
+use chrono::{Days, NaiveDate};
+
+/// Something in the tree that carries a date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dated {
+    Node(NodeId),
+    /// A node and the index of one of its tasks.
+    Task(NodeId, usize),
+}
+
+impl Dated {
+    pub fn node(self) -> NodeId {
+        match self {
+            Dated::Node(id) | Dated::Task(id, _) => id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    pub item: Dated,
+    pub date: NaiveDate,
+}
+
+impl GTree {
+    /// Every unfinished node and task with a date, earliest first.
+    pub fn deadlines(&self) -> Vec<Deadline> {
+        let mut out = Vec::new();
+        for node in &self.nodes {
+            if let Some(date) = node.target_date
+                && node.progress() < 1.0
+            {
+                out.push(Deadline { item: Dated::Node(node.id), date });
+            }
+            for (i, task) in node.tasks.iter().enumerate() {
+                if let Some(date) = task.due
+                    && task.progress() < 1.0
+                {
+                    out.push(Deadline { item: Dated::Task(node.id, i), date });
+                }
+            }
+        }
+        out.sort_by_key(|d| d.date);
+        out
+    }
+
+    /// Unfinished items whose date is before `today`.
+    pub fn overdue(&self, today: NaiveDate) -> Vec<Deadline> {
+        self.deadlines().into_iter().filter(|d| d.date < today).collect()
+    }
+
+    /// Unfinished items due from `today` up to `days` days later.
+    pub fn upcoming(&self, today: NaiveDate, days: u64) -> Vec<Deadline> {
+        let until = today + Days::new(days);
+        self.deadlines()
+            .into_iter()
+            .filter(|d| d.date >= today && d.date <= until)
+            .collect()
+    }
+
+    /// True if the node itself or one of its tasks is overdue.
+    pub fn is_overdue(&self, node: NodeId, today: NaiveDate) -> bool {
+        self.overdue(today).iter().any(|d| d.item.node() == node)
+    }
+}
@@ -0,0 +1,165 @@
+//! The error type for reading and writing tree files.
+
+use crate::validate::Diagnostic;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be read or written.
+    Io(io::Error),
+    /// The file is not valid JSON or does not have the shape of a tree.
+    /// `line` and `column` are 1-based; both are 0 when the problem was only
+    /// noticed after the document was migrated and positions are gone.
+    Parse { line: usize, column: usize, message: String },
+    /// The file was written with a format version this build does not know.
+    UnsupportedVersion { found: u64, supported: u64 },
+    /// The tree loaded but is broken; holds the error-level diagnostics.
+    Invalid(Vec<Diagnostic>),
+    /// One of a workspace's tree files failed to load or save.
+    Member { path: PathBuf, error: Box<Error> },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse { line: 0, message, .. } => write!(f, "not a valid tree file: {}", message),
+            Error::Parse { line, column, message } => {
+                write!(f, "not a valid tree file (line {}, column {}): {}", line, column, message)
+            }
+            Error::UnsupportedVersion { found, supported } => write!(
+                f,
+                "file has format version {}, but this build only reads up to {}",
+                found, supported
+            ),
+            Error::Invalid(diagnostics) => {
+                write!(f, "the tree has {} problem(s)", diagnostics.len())?;
+                for d in diagnostics {
+                    write!(f, "\n  {}", d)?;
+                }
+                Ok(())
+            }
+            Error::Member { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Member { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            return Error::Io(e.into());
+        }
+        // serde_json puts the position into its Display output; keep the message without it.
+        let message = e.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(i) if e.line() > 0 => message[..i].to_string(),
+            _ => message,
+        };
+        Error::Parse { line: e.line(), column: e.column(), message }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill_tree::{load_tree_from_file, NodeId};
+    use crate::validate::Problem;
+    use crate::workspace::load_workspace_from_file;
+    use std::fs;
+
+    /// Writes `contents` to a file of its own in the temp directory and returns its path.
+    fn write_temp(name: &str, contents: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("grind_trees_error_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn missing_file_is_io() {
+        let path = std::env::temp_dir().join("grind_trees_error_missing.json");
+        match load_tree_from_file(&path.to_string_lossy()) {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            other => panic!("expected Io, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bad_json_is_parse_with_a_position() {
+        let path = write_temp("bad_json.json", "{\n  \"title\": \"Broken\",\n  \"nodes\": [,]\n}");
+        match load_tree_from_file(&path) {
+            Err(Error::Parse { line, column, .. }) => assert_eq!((line, column), (3, 13)),
+            other => panic!("expected Parse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wrong_shape_keeps_its_position() {
+        let path = write_temp("bad_shape.json", "{\n  \"version\": 1,\n  \"title\": 5,\n  \"nodes\": []\n}");
+        match load_tree_from_file(&path) {
+            Err(e @ Error::Parse { line: 3, .. }) => assert!(e.to_string().contains("line 3"), "{}", e),
+            other => panic!("expected Parse on line 3, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn newer_file_is_unsupported_version() {
+        let path = write_temp("future.json", r#"{ "version": 99, "title": "Future", "nodes": [] }"#);
+        match load_tree_from_file(&path) {
+            Err(Error::UnsupportedVersion { found: 99, supported }) => {
+                assert_eq!(supported, crate::migrate::CURRENT_VERSION);
+            }
+            other => panic!("expected UnsupportedVersion, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn broken_tree_is_invalid() {
+        let path = write_temp(
+            "dangling.json",
+            r#"{ "version": 1, "title": "Dangling", "nodes": [{ "id": 0, "title": "a", "tasks": [] }],
+                "edges": [{ "from": 0, "to": 7, "kind": "requires" }] }"#,
+        );
+        match load_tree_from_file(&path) {
+            Err(Error::Invalid(diagnostics)) => {
+                let dangling = Problem::DanglingReference(NodeId(7));
+                assert!(diagnostics.iter().any(|d| d.problem == dangling), "{:?}", diagnostics);
+            }
+            other => panic!("expected Invalid, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failing_member_names_its_file() {
+        let path = write_temp(
+            "workspace.json",
+            r#"{ "version": 1, "name": "Work", "trees": [{ "id": 0, "path": "no_such_tree.json" }] }"#,
+        );
+        match load_workspace_from_file(&path) {
+            Err(Error::Member { path, error }) => {
+                assert!(path.ends_with("no_such_tree.json"));
+                assert!(matches!(*error, Error::Io(_)));
+            }
+            other => panic!("expected Member, got {:?}", other),
+        }
+    }
+}
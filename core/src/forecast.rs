@@ -0,0 +1,199 @@
+//! Effort estimates and when the remaining work is likely to be done.
+//!
+//! The pace is the estimated hours of work the activity log shows finished per day
+//! over the last few weeks. Remaining hours divided by that pace gives the forecast.
+
+use crate::activity::{Event, EventKind};
+use crate::skill_tree::{GNode, GTree, Measure, NodeId, TaskKind};
+use chrono::{Days, Local, NaiveDate};
+use std::collections::HashMap;
+
+/// How many days of activity the pace is taken over.
+pub const PACE_WINDOW: u64 = 28;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Forecast {
+    /// Estimated hours of work left.
+    pub remaining: f32,
+    /// Estimated hours finished per day recently.
+    pub hours_per_day: f32,
+    /// When the remaining work is done at that pace. `None` while nothing has been done lately.
+    pub finish: Option<NaiveDate>,
+}
+
+impl Forecast {
+    fn new(remaining: f32, hours_per_day: f32, today: NaiveDate) -> Self {
+        let finish = if remaining <= 0.0 {
+            Some(today)
+        } else if hours_per_day > 0.0 {
+            let days = (remaining / hours_per_day).ceil();
+            // Far enough out to be meaningless, and to overflow the calendar.
+            (days < 36_500.0).then(|| today + Days::new(days as u64))
+        } else {
+            None
+        };
+        Forecast { remaining, hours_per_day, finish }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.remaining <= 0.0
+    }
+}
+
+impl GNode {
+    /// Hours of the task at `index`: its own estimate, or else its weighted share of what the
+    /// node's estimate leaves after the estimated tasks.
+    pub fn task_hours(&self, index: usize) -> f32 {
+        let Some(task) = self.tasks.get(index) else { return 0.0 };
+        if let Some(hours) = task.estimate {
+            return hours;
+        }
+        let explicit: f32 = self.tasks.iter().filter_map(|t| t.estimate).sum();
+        let rest = (self.estimate.unwrap_or(0.0) - explicit).max(0.0);
+        let open: Vec<f32> = self.tasks.iter().filter(|t| t.estimate.is_none()).map(|t| t.weight).collect();
+        let total: f32 = open.iter().sum();
+        if total > 0.0 { rest * task.weight / total } else { rest / open.len() as f32 }
+    }
+
+    /// Hours the whole node takes. A node without tasks takes its own estimate.
+    pub fn estimated_hours(&self) -> f32 {
+        if self.tasks.is_empty() {
+            return self.estimate.unwrap_or(0.0);
+        }
+        (0..self.tasks.len()).map(|i| self.task_hours(i)).sum()
+    }
+
+    /// Hours of the work that isn't done yet.
+    pub fn remaining_hours(&self) -> f32 {
+        if self.tasks.is_empty() {
+            return self.estimated_hours();
+        }
+        self.tasks.iter().enumerate().map(|(i, t)| self.task_hours(i) * (1.0 - t.progress())).sum()
+    }
+}
+
+impl GTree {
+    /// Hours of estimated work finished per day over the `PACE_WINDOW` local days up to `today`,
+    /// or since the first recorded event if that is more recent.
+    pub fn pace(&self, today: NaiveDate) -> f32 {
+        // Events are stamped in UTC; `today` is a local day, like habit logs.
+        let day = |e: &Event| e.at.with_timezone(&Local).date_naive();
+        let Some(first) = self.activity().first().map(day) else { return 0.0 };
+        let start = (today - Days::new(PACE_WINDOW - 1)).max(first);
+        if start > today {
+            return 0.0;
+        }
+
+        // Measures report absolute readings, so keep the last one to count only the change.
+        let mut readings: HashMap<(NodeId, usize), f32> = HashMap::new();
+        let mut hours = 0.0;
+        for event in self.activity() {
+            let counted = day(event) >= start;
+            let done = match event.kind {
+                EventKind::TaskChecked { node, task } => self.hours_of(node, task, 1.0),
+                EventKind::TaskUnchecked { node, task } => self.hours_of(node, task, -1.0),
+                EventKind::TaskValueSet { node, task, value } => {
+                    let Some(TaskKind::Measure(m)) = self.node(node).and_then(|n| n.tasks.get(task)).map(|t| &t.kind)
+                    else {
+                        continue;
+                    };
+                    let before = readings.insert((node, task), value).unwrap_or(m.start);
+                    let fraction = |current| Measure { current, ..m.clone() }.fraction();
+                    self.hours_of(node, task, fraction(value) - fraction(before))
+                }
+                EventKind::HabitDone { node, task, .. } | EventKind::HabitUndone { node, task, .. } => {
+                    let Some(TaskKind::Habit(h)) = self.node(node).and_then(|n| n.tasks.get(task)).map(|t| &t.kind)
+                    else {
+                        continue;
+                    };
+                    let sign = if matches!(event.kind, EventKind::HabitDone { .. }) { 1.0 } else { -1.0 };
+                    self.hours_of(node, task, sign / h.target_streak.max(1) as f32)
+                }
+                _ => 0.0,
+            };
+            if counted {
+                hours += done;
+            }
+        }
+        let days = (today - start).num_days() + 1;
+        (hours / days as f32).max(0.0)
+    }
+
+    /// `share` of the hours of a task, or nothing for a task that is gone.
+    fn hours_of(&self, node: NodeId, task: usize, share: f32) -> f32 {
+        self.node(node).map_or(0.0, |n| n.task_hours(task) * share)
+    }
+
+    /// Hours the whole tree takes.
+    pub fn estimated_hours(&self) -> f32 {
+        self.nodes.iter().map(GNode::estimated_hours).sum()
+    }
+
+    /// Forecast for the work left on `ids` at the current pace. Nodes without estimates don't
+    /// count; `None` if none of them has one.
+    pub fn forecast_of(&self, ids: impl IntoIterator<Item = NodeId>, today: NaiveDate) -> Option<Forecast> {
+        let nodes: Vec<&GNode> = ids.into_iter().filter_map(|id| self.node(id)).collect();
+        if nodes.iter().all(|n| n.estimated_hours() <= 0.0) {
+            return None;
+        }
+        let remaining = nodes.iter().map(|n| n.remaining_hours()).sum();
+        Some(Forecast::new(remaining, self.pace(today), today))
+    }
+
+    /// Forecast for the whole tree.
+    pub fn forecast(&self, today: NaiveDate) -> Option<Forecast> {
+        self.forecast_of(self.nodes.iter().map(|n| n.id), today)
+    }
+
+    /// Forecast for `node`, which can't be done before everything it needs is.
+    pub fn node_forecast(&self, node: NodeId, today: NaiveDate) -> Option<Forecast> {
+        self.node(node)?;
+        self.forecast_of(self.prerequisite_closure(node), today)
+    }
+
+    /// Forecast for the branch rooted at `root`, as in `subtree_progress`.
+    pub fn branch_forecast(&self, root: NodeId, today: NaiveDate) -> Option<Forecast> {
+        self.forecast_of(self.subtree(root), today)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill_tree::{Mode, Task};
+
+    fn node_with(estimate: Option<f32>, tasks: &[Option<f32>]) -> GNode {
+        let mut node = GNode::new("Node");
+        node.estimate = estimate;
+        for &hours in tasks {
+            let mut task = Task::new("Task");
+            task.estimate = hours;
+            node.tasks.push(task);
+        }
+        node
+    }
+
+    #[test]
+    fn unestimated_tasks_share_the_rest_of_the_node_estimate() {
+        let node = node_with(Some(10.0), &[Some(4.0), None, None]);
+        assert_eq!(node.task_hours(0), 4.0);
+        assert_eq!(node.task_hours(1), 3.0);
+        assert_eq!(node.task_hours(2), 3.0);
+        assert_eq!(node.estimated_hours(), 10.0);
+    }
+
+    #[test]
+    fn forecast_follows_the_pace_of_checked_tasks() {
+        let mut tree = GTree::new("Tree");
+        let id = tree.add_node(node_with(None, &[Some(2.0), Some(6.0)]));
+        let today = crate::habit::today();
+        assert_eq!(tree.forecast(today).unwrap().finish, None);
+
+        tree.set_task_checked(Mode::Grind, id, 0, true).unwrap();
+        let forecast = tree.forecast(today).unwrap();
+        assert_eq!(forecast.remaining, 6.0);
+        assert_eq!(forecast.hours_per_day, 2.0);
+        assert_eq!(forecast.finish, Some(today + Days::new(3)));
+        assert!(GTree::new("Empty").forecast(today).is_none());
+    }
+}
@@ -0,0 +1,206 @@
+//! Copying a branch of one tree into another.
+
+use crate::skill_tree::{Edge, EdgeKind, GTree, NodeId};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// How far above its new parent a grafted branch starts, in parent radii.
+const GRAFT_OFFSET: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraftError {
+    /// A root that doesn't exist in the source tree.
+    UnknownSource(NodeId),
+    /// The node to graft under doesn't exist in this tree.
+    UnknownTarget(NodeId),
+}
+
+impl fmt::Display for GraftError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraftError::UnknownSource(id) => write!(f, "node {} does not exist in the imported tree", id),
+            GraftError::UnknownTarget(id) => write!(f, "node {} does not exist", id),
+        }
+    }
+}
+
+impl std::error::Error for GraftError {}
+
+/// What `GTree::graft` did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graft {
+    /// Source id to new id, in the order the nodes were added.
+    pub ids: Vec<(NodeId, NodeId)>,
+    /// New ids of the nodes whose title was taken and got a number appended.
+    pub renamed: Vec<NodeId>,
+}
+
+impl GTree {
+    /// Copies `roots` and everything building on them from `source` into this tree. Nodes get fresh
+    /// ids, titles already in use get a " (2)", " (3)", ... suffix, and each root requires `under`
+    /// when given. Edges between copied nodes come along; edges to nodes left behind don't.
+    pub fn graft(&mut self, source: &GTree, roots: &[NodeId], under: Option<NodeId>) -> Result<Graft, GraftError> {
+        if let Some(&missing) = roots.iter().find(|&&r| source.node(r).is_none()) {
+            return Err(GraftError::UnknownSource(missing));
+        }
+        let anchor = match under {
+            Some(id) => Some(self.node(id).ok_or(GraftError::UnknownTarget(id))?),
+            None => None,
+        };
+
+        let mut picked: Vec<NodeId> = Vec::new();
+        let mut seen = HashSet::new();
+        for &root in roots {
+            for id in source.subtree(root) {
+                if seen.insert(id) {
+                    picked.push(id);
+                }
+            }
+        }
+        // Keep the source's order so the copy lists the same way.
+        picked.sort_by_key(|&id| source.index_of(id));
+
+        // Shift the copy so the first root sits above `under`, or right of everything else.
+        let first = source.node(roots.first().copied().unwrap_or(NodeId(0)));
+        let (dx, dy) = match (anchor, first) {
+            (Some(anchor), Some(first)) => (anchor.x - first.x, anchor.y - GRAFT_OFFSET * anchor.r - first.y),
+            (None, Some(_)) if !self.nodes.is_empty() => {
+                let right = self.nodes.iter().map(|n| n.x + n.r).fold(f32::MIN, f32::max);
+                let left = picked.iter().filter_map(|&id| source.node(id)).map(|n| n.x - n.r).fold(f32::MAX, f32::min);
+                (right + 100.0 - left, 0.0)
+            }
+            _ => (0.0, 0.0),
+        };
+
+        let mut titles: HashSet<String> = self.nodes.iter().map(|n| n.title.to_lowercase()).collect();
+        let mut graft = Graft::default();
+        let mut map = HashMap::new();
+        for &old in &picked {
+            let mut node = source.node(old).expect("picked from source").clone();
+            let title = unique_title(&node.title, &titles);
+            let renamed = title != node.title;
+            titles.insert(title.to_lowercase());
+            node.title = title;
+            node.x += dx;
+            node.y += dy;
+            let new = self.add_node(node);
+            if renamed {
+                graft.renamed.push(new);
+            }
+            map.insert(old, new);
+            graft.ids.push((old, new));
+        }
+
+        for edge in &source.edges {
+            if let (Some(&from), Some(&to)) = (map.get(&edge.from), map.get(&edge.to)) {
+                self.edges.push(Edge { from, to, kind: edge.kind });
+            }
+        }
+        if let Some(under) = under {
+            for root in roots {
+                let root = map[root];
+                // A root reachable from another root already hangs below `under`.
+                if self.edge(under, root).is_none() && !self.edges.iter().any(|e| e.to == root && e.kind == EdgeKind::Requires) {
+                    self.edges.push(Edge { from: under, to: root, kind: EdgeKind::Requires });
+                }
+            }
+        }
+        Ok(graft)
+    }
+}
+
+/// `title`, or `title (n)` with the smallest n from 2 up that isn't in `taken` (lowercased titles).
+fn unique_title(title: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(&title.to_lowercase()) {
+        return title.to_string();
+    }
+    (2..)
+        .map(|n| format!("{} ({})", title, n))
+        .find(|t| !taken.contains(&t.to_lowercase()))
+        .expect("some suffix is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill_tree::GNode;
+
+    /// a -> b -> c, a -> d, and a loose e that recommends c.
+    fn source() -> (GTree, [NodeId; 5]) {
+        let mut tree = GTree::new("Source");
+        let a = tree.add_node(GNode::new("a"));
+        let b = tree.add_child(a, GNode::new("b")).unwrap();
+        let c = tree.add_child(b, GNode::new("c")).unwrap();
+        let d = tree.add_child(a, GNode::new("d")).unwrap();
+        let e = tree.add_node(GNode::new("e"));
+        tree.add_edge(e, c, EdgeKind::Recommends).unwrap();
+        (tree, [a, b, c, d, e])
+    }
+
+    fn target(titles: &[&str]) -> GTree {
+        let mut tree = GTree::new("Target");
+        for title in titles {
+            tree.add_node(GNode::new(*title));
+        }
+        tree
+    }
+
+    fn edges(tree: &GTree) -> Vec<(NodeId, NodeId, EdgeKind)> {
+        tree.edges.iter().map(|e| (e.from, e.to, e.kind)).collect()
+    }
+
+    #[test]
+    fn taken_titles_get_a_suffix() {
+        let (source, [_, b, _, _, _]) = source();
+        let mut tree = target(&["B", "b (2)", "C"]);
+        let graft = tree.graft(&source, &[b], None).unwrap();
+        let titles: Vec<&str> = graft.ids.iter().map(|&(_, new)| tree.node(new).unwrap().title.as_str()).collect();
+        assert_eq!(titles, ["b (3)", "c (2)"]);
+        assert_eq!(graft.renamed, graft.ids.iter().map(|&(_, new)| new).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn copies_get_fresh_ids_and_keep_only_inner_edges() {
+        let (source, [_, b, c, _, _]) = source();
+        let mut tree = target(&["x", "y"]);
+        let graft = tree.graft(&source, &[b], None).unwrap();
+        let (new_b, new_c) = (NodeId(2), NodeId(3));
+        assert_eq!(graft.ids, [(b, new_b), (c, new_c)]);
+        assert!(graft.renamed.is_empty());
+        // a -> b and e -> c point at nodes that stayed behind.
+        assert_eq!(edges(&tree), [(new_b, new_c, EdgeKind::Requires)]);
+        assert!(tree.validate().is_empty());
+    }
+
+    #[test]
+    fn each_root_hangs_below_under_once() {
+        let (source, [_, b, c, d, _]) = source();
+        let mut tree = target(&["t"]);
+        let t = tree.nodes[0].id;
+        let graft = tree.graft(&source, &[b, d, b], Some(t)).unwrap();
+        let new = |old| graft.ids.iter().find(|&&(o, _)| o == old).unwrap().1;
+        for root in [b, d] {
+            let under: Vec<_> = tree.edges.iter().filter(|e| e.to == new(root)).map(|e| (e.from, e.kind)).collect();
+            assert_eq!(under, [(t, EdgeKind::Requires)]);
+        }
+        assert_eq!(tree.edges.iter().filter(|e| e.from == t).count(), 2);
+
+        // A root below another root already depends on `under` through it.
+        let mut tree = target(&["t"]);
+        let graft = tree.graft(&source, &[b, c], Some(t)).unwrap();
+        let new_c = graft.ids.iter().find(|&&(o, _)| o == c).unwrap().1;
+        let into_c: Vec<_> = tree.edges.iter().filter(|e| e.to == new_c).map(|e| e.from).collect();
+        assert_eq!(into_c.len(), 1);
+        assert_ne!(into_c[0], t);
+    }
+
+    #[test]
+    fn missing_nodes_change_nothing() {
+        let (source, [a, ..]) = source();
+        let mut tree = target(&["t"]);
+        assert_eq!(tree.graft(&source, &[NodeId(42)], None), Err(GraftError::UnknownSource(NodeId(42))));
+        assert_eq!(tree.graft(&source, &[a], Some(NodeId(7))), Err(GraftError::UnknownTarget(NodeId(7))));
+        assert_eq!(tree.nodes.len(), 1);
+        assert!(tree.edges.is_empty());
+    }
+}
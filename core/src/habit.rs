@@ -0,0 +1,241 @@
+//! Recurring tasks that are done again and again rather than once.
+//!
+//! A habit keeps a log of the days it was done. Depending on its schedule the
+//! days are grouped into periods (single days, or Monday-based weeks), and a
+//! streak is the number of consecutive periods in which the habit was done
+//! often enough.
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    Daily,
+    /// Monday to Friday; weekends neither count nor break a streak.
+    Weekdays,
+    /// At least this many days in every week.
+    TimesPerWeek(u32),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Habit {
+    pub schedule: Schedule,
+    /// Days the habit was done, sorted and without duplicates.
+    #[serde(default)]
+    pub log: Vec<NaiveDate>,
+    /// The streak, in periods, after which the task counts as done.
+    #[serde(default = "default_target_streak")]
+    pub target_streak: u32,
+}
+
+fn default_target_streak() -> u32 {
+    7
+}
+
+/// The local calendar day, which is what habit logs are kept in.
+pub fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+fn is_weekend(day: NaiveDate) -> bool {
+    matches!(day.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+impl Habit {
+    pub fn new(schedule: Schedule) -> Self {
+        Habit { schedule, log: Vec::new(), target_streak: default_target_streak() }
+    }
+
+    pub fn done_on(&self, day: NaiveDate) -> bool {
+        self.log.binary_search(&day).is_ok()
+    }
+
+    pub fn set_done(&mut self, day: NaiveDate, done: bool) {
+        match (self.log.binary_search(&day), done) {
+            (Err(i), true) => self.log.insert(i, day),
+            (Ok(i), false) => {
+                self.log.remove(i);
+            }
+            _ => {}
+        }
+    }
+
+    /// True if the habit still wants doing on `today`.
+    pub fn is_due(&self, today: NaiveDate) -> bool {
+        if self.done_on(today) {
+            return false;
+        }
+        match self.schedule {
+            Schedule::Daily => true,
+            Schedule::Weekdays => !is_weekend(today),
+            Schedule::TimesPerWeek(times) => self.done_in_week(today) < times,
+        }
+    }
+
+    /// Consecutive periods up to `today` in which the habit was kept up.
+    /// The running period only adds to the streak once it is met; until then it doesn't break it either.
+    pub fn current_streak(&self, today: NaiveDate) -> u32 {
+        let Some(&first) = self.log.first() else {
+            return 0;
+        };
+        let mut period = self.period_start(today);
+        if !self.period_met(period) {
+            period = self.previous_period(period);
+        }
+        let mut streak = 0;
+        while period >= self.period_start(first) && self.period_met(period) {
+            streak += 1;
+            period = self.previous_period(period);
+        }
+        streak
+    }
+
+    /// The longest run of kept-up periods anywhere in the log.
+    pub fn best_streak(&self) -> u32 {
+        let (Some(&first), Some(&last)) = (self.log.first(), self.log.last()) else {
+            return 0;
+        };
+        let first = self.period_start(first);
+        let mut period = self.period_start(last);
+        let (mut best, mut run) = (0, 0);
+        while period >= first {
+            if self.period_met(period) {
+                run += 1;
+                best = best.max(run);
+            } else {
+                run = 0;
+            }
+            period = self.previous_period(period);
+        }
+        best
+    }
+
+    /// Best streak measured against the target, 0..1.
+    pub fn progress(&self) -> f32 {
+        if self.target_streak == 0 {
+            return 1.0;
+        }
+        (self.best_streak() as f32 / self.target_streak as f32).min(1.0)
+    }
+
+    fn done_in_week(&self, day: NaiveDate) -> u32 {
+        let monday = day - Days::new(day.weekday().num_days_from_monday() as u64);
+        let sunday = monday + Days::new(6);
+        self.log.iter().filter(|&&d| d >= monday && d <= sunday).count() as u32
+    }
+
+    /// First day of the period containing `day`.
+    fn period_start(&self, day: NaiveDate) -> NaiveDate {
+        match self.schedule {
+            Schedule::Daily => day,
+            // A weekend belongs to the Friday before it.
+            Schedule::Weekdays => match day.weekday() {
+                Weekday::Sat => day - Days::new(1),
+                Weekday::Sun => day - Days::new(2),
+                _ => day,
+            },
+            Schedule::TimesPerWeek(_) => day - Days::new(day.weekday().num_days_from_monday() as u64),
+        }
+    }
+
+    fn previous_period(&self, start: NaiveDate) -> NaiveDate {
+        match self.schedule {
+            Schedule::Daily => start - Days::new(1),
+            Schedule::Weekdays => self.period_start(start - Days::new(1)),
+            Schedule::TimesPerWeek(_) => start - Days::new(7),
+        }
+    }
+
+    fn period_met(&self, start: NaiveDate) -> bool {
+        match self.schedule {
+            Schedule::Daily | Schedule::Weekdays => self.done_on(start),
+            Schedule::TimesPerWeek(times) => self.done_in_week(start) >= times,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A day in March 2024; the 4th is a Monday.
+    fn march(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+    }
+
+    fn habit(schedule: Schedule, days: &[u32]) -> Habit {
+        let mut habit = Habit::new(schedule);
+        for &day in days {
+            habit.set_done(march(day), true);
+        }
+        habit
+    }
+
+    #[test]
+    fn empty_log_has_no_streak_and_is_due() {
+        let habit = Habit::new(Schedule::Daily);
+        assert_eq!(habit.current_streak(march(4)), 0);
+        assert_eq!(habit.best_streak(), 0);
+        assert!(habit.is_due(march(4)));
+    }
+
+    #[test]
+    fn daily_streak_breaks_on_a_missed_day() {
+        let habit = habit(Schedule::Daily, &[4, 5, 6, 8]);
+        assert_eq!(habit.current_streak(march(8)), 1);
+        // Today not done yet doesn't break the streak; yesterday missed does.
+        assert_eq!(habit.current_streak(march(9)), 1);
+        assert_eq!(habit.current_streak(march(10)), 0);
+        assert_eq!(habit.current_streak(march(6)), 3);
+        assert_eq!(habit.best_streak(), 3);
+        assert!(!habit.is_due(march(8)));
+        assert!(habit.is_due(march(9)));
+        assert!(habit.is_due(march(10)));
+    }
+
+    #[test]
+    fn weekends_neither_count_nor_break_a_weekday_streak() {
+        // Thursday, Friday, then Monday.
+        let kept = habit(Schedule::Weekdays, &[7, 8, 11]);
+        assert_eq!(kept.current_streak(march(9)), 2);
+        assert_eq!(kept.current_streak(march(10)), 2);
+        assert_eq!(kept.current_streak(march(11)), 3);
+        assert_eq!(kept.current_streak(march(12)), 3);
+        assert_eq!(kept.best_streak(), 3);
+        assert!(!kept.is_due(march(9)));
+        assert!(!kept.is_due(march(10)));
+        assert!(!kept.is_due(march(11)));
+        assert!(kept.is_due(march(12)));
+
+        // Skipping Friday breaks it, and weekend days don't make up for it.
+        let skipped = habit(Schedule::Weekdays, &[7, 9, 10, 11]);
+        assert_eq!(skipped.current_streak(march(11)), 1);
+        assert_eq!(skipped.best_streak(), 1);
+    }
+
+    #[test]
+    fn times_per_week_counts_monday_to_sunday() {
+        // Monday and Sunday of one week, Monday and Wednesday of the next.
+        let mut habit = habit(Schedule::TimesPerWeek(2), &[4, 10, 11, 13]);
+        assert_eq!(habit.current_streak(march(13)), 2);
+        assert!(!habit.is_due(march(14)));
+        assert!(!habit.is_due(march(17)));
+        // A new week that isn't met yet keeps the streak going.
+        assert!(habit.is_due(march(18)));
+        assert_eq!(habit.current_streak(march(18)), 2);
+
+        habit.set_done(march(18), true);
+        assert!(habit.is_due(march(19)));
+        habit.set_done(march(24), true);
+        assert!(!habit.is_due(march(20)));
+        assert_eq!(habit.current_streak(march(24)), 3);
+
+        // A week with a single day doesn't count.
+        habit.set_done(march(25), true);
+        habit.set_done(NaiveDate::from_ymd_opt(2024, 4, 1).unwrap(), true);
+        habit.set_done(NaiveDate::from_ymd_opt(2024, 4, 2).unwrap(), true);
+        assert_eq!(habit.current_streak(NaiveDate::from_ymd_opt(2024, 4, 2).unwrap()), 1);
+        assert_eq!(habit.best_streak(), 3);
+    }
+}
@@ -1 +1,16 @@
+pub mod achievement;
+pub mod activity;
+pub mod command;
+pub mod deadline;
+pub mod error;
+pub mod forecast;
+pub mod graft;
+pub mod habit;
+pub mod markdown;
+pub mod migrate;
+pub mod resource;
 pub mod skill_tree;
+pub mod tags;
+pub mod template;
+pub mod validate;
+pub mod workspace;
@@ -0,0 +1,351 @@
+//! The Markdown subset used in node descriptions, and a renderer-agnostic layout for it.
+//!
+//! Supported: `#` headings, `-`/`*`/`+` bullet lists (indent by two spaces to nest),
+//! `**bold**`, `*italic*`, `` `code` `` and `[links](url)`. Everything else is plain text.
+
+/// How a run of text is styled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+}
+
+/// Text with a single style, optionally part of a link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+    pub link: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    /// Level 1 to 6.
+    Heading { level: u8, spans: Vec<Span> },
+    Paragraph(Vec<Span>),
+    /// A list item; `depth` is 0 for top level items.
+    Bullet { depth: usize, spans: Vec<Span> },
+}
+
+/// Splits `src` into blocks. Lines of a paragraph are joined with a space; blank lines end it.
+pub fn parse(src: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let flush = |paragraph: &mut Vec<&str>, blocks: &mut Vec<Block>| {
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(parse_inline(&paragraph.join(" "))));
+            paragraph.clear();
+        }
+    };
+    for line in src.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() {
+            flush(&mut paragraph, &mut blocks);
+        } else if let Some((level, text)) = heading(trimmed) {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Heading { level, spans: parse_inline(text) });
+        } else if let Some(text) = ["- ", "* ", "+ "].iter().find_map(|m| trimmed.strip_prefix(m)) {
+            flush(&mut paragraph, &mut blocks);
+            let indent = line.len() - trimmed.len();
+            blocks.push(Block::Bullet { depth: indent / 2, spans: parse_inline(text.trim()) });
+        } else {
+            paragraph.push(line.trim());
+        }
+    }
+    flush(&mut paragraph, &mut blocks);
+    blocks
+}
+
+fn heading(line: &str) -> Option<(u8, &str)> {
+    let level = line.bytes().take_while(|&b| b == b'#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &line[level..];
+    if rest.is_empty() {
+        return Some((level as u8, ""));
+    }
+    rest.strip_prefix(' ').map(|text| (level as u8, text.trim()))
+}
+
+/// Parses emphasis, code and links in a single line of text.
+pub fn parse_inline(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    inline(text, Style::default(), None, &mut spans);
+    spans
+}
+
+fn inline(text: &str, style: Style, link: Option<&str>, out: &mut Vec<Span>) {
+    let mut plain = String::new();
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap();
+        // (length of the markup, inner text, style and link of the inner text)
+        let markup = if c == '`' {
+            rest[1..].find('`').map(|end| (end + 2, &rest[1..end + 1], Style { code: true, ..style }, link))
+        } else if rest.starts_with("**") || rest.starts_with("__") {
+            let marker = &rest[..2];
+            find_double(&rest[2..], marker)
+                .filter(|&end| end > 0)
+                .map(|end| (end + 4, &rest[2..end + 2], Style { bold: true, ..style }, link))
+        } else if (c == '*' || c == '_') && !(c == '_' && ends_in_word(&text[..i])) {
+            find_single(&rest[1..], c)
+                .filter(|&end| end > 0)
+                .map(|end| (end + 2, &rest[1..end + 1], Style { italic: true, ..style }, link))
+        } else if c == '[' {
+            rest.find("](").and_then(|mid| {
+                rest[mid + 2..].find(')').map(|end| (mid + end + 3, &rest[1..mid], style, Some(&rest[mid + 2..mid + 2 + end])))
+            })
+        } else {
+            None
+        };
+        match markup {
+            Some((len, inner, inner_style, inner_link)) => {
+                push(out, std::mem::take(&mut plain), style, link);
+                if inner_style.code {
+                    push(out, inner.to_string(), inner_style, inner_link);
+                } else {
+                    inline(inner, inner_style, inner_link, out);
+                }
+                i += len;
+            }
+            None => {
+                plain.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    push(out, plain, style, link);
+}
+
+fn ends_in_word(text: &str) -> bool {
+    text.chars().next_back().is_some_and(char::is_alphanumeric)
+}
+
+/// Finds the closing `marker` of bold text. In a run like `***` the last two close it,
+/// so `**a *b***` ends italic text first.
+fn find_double(text: &str, marker: &str) -> Option<usize> {
+    let m = marker.as_bytes()[0];
+    text.char_indices()
+        .map(|(i, _)| i)
+        .find(|&i| text[i..].starts_with(marker) && text.as_bytes().get(i + 2) != Some(&m))
+}
+
+/// Finds a lone `marker`, skipping doubled ones that belong to bold text.
+fn find_single(text: &str, marker: char) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let m = marker as u8;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == m {
+            if bytes.get(i + 1) == Some(&m) {
+                i += 2;
+                continue;
+            }
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Appends text, merging it into the previous span when that has the same style and link.
+fn push(out: &mut Vec<Span>, text: String, style: Style, link: Option<&str>) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(last) = out.last_mut()
+        && last.style == style
+        && last.link.as_deref() == link
+    {
+        last.text.push_str(&text);
+        return;
+    }
+    out.push(Span { text, style, link: link.map(str::to_string) });
+}
+
+/// Text placed at an x offset within its line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub x: f32,
+    pub text: String,
+    pub style: Style,
+    pub link: Option<String>,
+    pub size: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    /// Offset of the text baseline from the top of the layout.
+    pub baseline: f32,
+    pub runs: Vec<Run>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Layout {
+    pub lines: Vec<Line>,
+    pub height: f32,
+}
+
+/// Font size of a heading relative to body text.
+fn heading_scale(level: u8) -> f32 {
+    match level {
+        1 => 1.5,
+        2 => 1.3,
+        3 => 1.15,
+        _ => 1.0,
+    }
+}
+
+/// Word-wraps `blocks` into lines no wider than `max_width`, using `measure(text, style, size)`
+/// for the width of a piece of text. Words wider than a whole line are left to overflow.
+pub fn layout(blocks: &[Block], max_width: f32, font_size: f32, measure: impl Fn(&str, Style, f32) -> f32) -> Layout {
+    let mut out = Layout::default();
+    let line_gap = 0.3 * font_size;
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 && !matches!((&blocks[i - 1], block), (Block::Bullet { .. }, Block::Bullet { .. })) {
+            out.height += 0.5 * font_size;
+        }
+        let (spans, size, indent, bullet) = match block {
+            Block::Heading { level, spans } => {
+                let bold = spans.iter().map(|s| Span { style: Style { bold: true, ..s.style }, ..s.clone() }).collect();
+                (bold, font_size * heading_scale(*level), 0.0, false)
+            }
+            Block::Paragraph(spans) => (spans.clone(), font_size, 0.0, false),
+            Block::Bullet { depth, spans } => (spans.clone(), font_size, font_size * (1.0 + *depth as f32), true),
+        };
+        let start = out.lines.len();
+        wrap(&spans, max_width - indent, size, &measure, &mut out.lines);
+        if out.lines.len() == start {
+            out.lines.push(Line { baseline: 0.0, runs: Vec::new() });
+        }
+        for (n, line) in out.lines[start..].iter_mut().enumerate() {
+            out.height += size;
+            line.baseline = out.height;
+            out.height += line_gap;
+            for run in &mut line.runs {
+                run.x += indent;
+            }
+            if bullet && n == 0 {
+                let x = indent - measure("- ", Style::default(), size);
+                line.runs.insert(0, Run { x, text: "-".to_string(), style: Style::default(), link: None, size });
+            }
+        }
+    }
+    out.height -= line_gap.min(out.height);
+    out
+}
+
+/// Greedy word wrap of styled spans. Words may change style midway, e.g. "**bold**,".
+fn wrap(spans: &[Span], max_width: f32, size: f32, measure: &impl Fn(&str, Style, f32) -> f32, lines: &mut Vec<Line>) {
+    // Each word is a list of (span index, text) pieces.
+    let mut words: Vec<Vec<(usize, &str)>> = Vec::new();
+    let mut joined = false;
+    for (s, span) in spans.iter().enumerate() {
+        let mut rest = span.text.as_str();
+        while !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end > 0 {
+                match words.last_mut() {
+                    Some(word) if joined => word.push((s, &rest[..end])),
+                    _ => words.push(vec![(s, &rest[..end])]),
+                }
+            }
+            rest = &rest[end..];
+            let trimmed = rest.trim_start();
+            joined = trimmed.len() == rest.len();
+            rest = trimmed;
+        }
+    }
+
+    let mut line = Line { baseline: 0.0, runs: Vec::new() };
+    let mut x = 0.0;
+    for word in words {
+        let width: f32 = word.iter().map(|&(s, t)| measure(t, spans[s].style, size)).sum();
+        let space = match line.runs.last() {
+            Some(run) => measure(" ", run.style, size),
+            None => 0.0,
+        };
+        if !line.runs.is_empty() && x + space + width > max_width {
+            lines.push(std::mem::replace(&mut line, Line { baseline: 0.0, runs: Vec::new() }));
+            x = 0.0;
+        } else {
+            x += space;
+        }
+        for (n, &(s, text)) in word.iter().enumerate() {
+            let span = &spans[s];
+            match line.runs.last_mut() {
+                Some(run) if run.style == span.style && run.link == span.link => {
+                    if n == 0 {
+                        run.text.push(' ');
+                    }
+                    run.text.push_str(text);
+                }
+                _ => line.runs.push(Run { x, text: text.to_string(), style: span.style, link: span.link.clone(), size }),
+            }
+            x += measure(text, span.style, size);
+        }
+    }
+    if !line.runs.is_empty() {
+        lines.push(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(text: &str, style: Style) -> Span {
+        Span { text: text.to_string(), style, link: None }
+    }
+
+    const BOLD: Style = Style { bold: true, italic: false, code: false };
+    const ITALIC: Style = Style { bold: false, italic: true, code: false };
+    const CODE: Style = Style { bold: false, italic: false, code: true };
+
+    #[test]
+    fn parses_blocks() {
+        let blocks = parse("# Title\nfirst line\nsecond line\n\n- one\n  - nested\n");
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Heading { level: 1, spans: vec![span("Title", Style::default())] },
+                Block::Paragraph(vec![span("first line second line", Style::default())]),
+                Block::Bullet { depth: 0, spans: vec![span("one", Style::default())] },
+                Block::Bullet { depth: 1, spans: vec![span("nested", Style::default())] },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_inline_styles() {
+        let spans = parse_inline("a **b *c*** `d*e*` and [site](http://x.y)");
+        assert_eq!(spans[0], span("a ", Style::default()));
+        assert_eq!(spans[1], span("b ", BOLD));
+        assert_eq!(spans[2], span("c", Style { italic: true, ..BOLD }));
+        assert_eq!(spans[3], span(" ", Style::default()));
+        assert_eq!(spans[4], span("d*e*", CODE));
+        assert_eq!(spans[6], Span { text: "site".into(), style: Style::default(), link: Some("http://x.y".into()) });
+        assert_eq!(parse_inline("**café** *日本語*"), vec![span("café", BOLD), span(" ", Style::default()), span("日本語", ITALIC)]);
+    }
+
+    #[test]
+    fn leaves_unmatched_markers_alone() {
+        assert_eq!(parse_inline("2 * 3 and snake_case_name"), vec![span("2 * 3 and snake_case_name", Style::default())]);
+        assert_eq!(parse_inline("_it_"), vec![span("it", ITALIC)]);
+        assert_eq!(parse_inline("#hashtag"), vec![span("#hashtag", Style::default())]);
+    }
+
+    #[test]
+    fn wraps_at_max_width() {
+        // Every character is 1 wide.
+        let measure = |text: &str, _: Style, _: f32| text.chars().count() as f32;
+        let layout = layout(&parse("aaa **bb** cc dddd"), 7.0, 10.0, measure);
+        let lines: Vec<Vec<&str>> = layout.lines.iter().map(|l| l.runs.iter().map(|r| r.text.as_str()).collect()).collect();
+        assert_eq!(lines, vec![vec!["aaa", "bb"], vec!["cc dddd"]]);
+        assert_eq!(layout.lines[0].runs[1].x, 4.0);
+        assert_eq!(layout.lines[1].baseline, 23.0);
+    }
+}
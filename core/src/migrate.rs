@@ -0,0 +1,135 @@
+//! Upgrades saved documents from older shapes to the current schema.
+//!
+//! Every file written by `save_tree_to_file` carries a `version`. Files without
+//! one predate versioning and come in a few shapes, all handled as version 0:
+//! - the first core format (`title`, `description`, stored `progress`/`is_lit`),
+//! - the goals format (`goals`, `x`/`y`/`r`, no description),
+//! - the legacy viewer format (`x`/`y`/`r`, `parent`, `tasks` possibly null),
+//! - core files with node ids and `edges` but no version yet.
+
+use crate::error::Error;
+use serde_json::{Map, Value};
+
+/// The schema `save_tree_to_file` writes.
+pub const CURRENT_VERSION: u64 = 1;
+
+fn not_a_tree() -> Error {
+    Error::Parse { line: 0, column: 0, message: "document is not a skill tree".into() }
+}
+
+/// The format version a parsed document claims; unversioned files count as 0.
+pub fn version_of(doc: &Value) -> u64 {
+    doc.get("version").and_then(Value::as_u64).unwrap_or(0)
+}
+
+/// Brings a parsed document up to `CURRENT_VERSION`, one version step at a time.
+pub fn migrate(doc: &mut Value) -> Result<(), Error> {
+    let version = version_of(doc);
+    let obj = doc.as_object_mut().ok_or_else(not_a_tree)?;
+    if version > CURRENT_VERSION {
+        return Err(Error::UnsupportedVersion { found: version, supported: CURRENT_VERSION });
+    }
+    if version < 1 {
+        v0_to_v1(obj)?;
+    }
+    obj.insert("version".into(), CURRENT_VERSION.into());
+    Ok(())
+}
+
+fn v0_to_v1(tree: &mut Map<String, Value>) -> Result<(), Error> {
+    // Progress is derived now, stored values are dropped rather than trusted.
+    tree.remove("progress");
+    let nodes = tree
+        .get_mut("nodes")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(not_a_tree)?;
+
+    let mut edges = Vec::new();
+    for (i, node) in nodes.iter_mut().enumerate() {
+        let node = node.as_object_mut().ok_or_else(not_a_tree)?;
+        node.remove("progress");
+        node.remove("is_lit");
+        // Files without ids address nodes by position, so the position becomes the id
+        // and any `parent` index keeps pointing at the same node.
+        let id = node.entry("id").or_insert(i.into()).clone();
+        if node.get("tasks").is_none_or(Value::is_null) {
+            node.insert("tasks".into(), Value::Array(Vec::new()));
+        }
+        // The legacy single parent becomes a regular prerequisite edge; negative means none.
+        if let Some(parent) = node.remove("parent").as_ref().and_then(Value::as_u64) {
+            edges.push(serde_json::json!({ "from": parent, "to": id, "kind": "requires" }));
+        }
+    }
+
+    match tree.get_mut("edges").and_then(Value::as_array_mut) {
+        Some(existing) => existing.extend(edges),
+        None => {
+            tree.insert("edges".into(), Value::Array(edges));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill_tree::{load_tree_from_file, EdgeKind, GTree, NodeId};
+
+    fn load_save(name: &str) -> GTree {
+        let path = format!("{}/../saves/{}", env!("CARGO_MANIFEST_DIR"), name);
+        load_tree_from_file(&path).unwrap_or_else(|e| panic!("{}: {}", name, e))
+    }
+
+    #[test]
+    fn first_core_format_loads() {
+        let tree = load_save("data.json");
+        assert_eq!(tree.title, "Rust Adventure");
+        assert_eq!(tree.nodes.len(), 1);
+        assert!(tree.nodes[0].tasks[0].checked);
+        assert!(tree.edges.is_empty());
+    }
+
+    #[test]
+    fn legacy_parents_become_requires_edges() {
+        for name in ["state.json", "new_state.json"] {
+            let tree = load_save(name);
+            assert_eq!(tree.nodes.len(), 10, "{}", name);
+            let ids: Vec<NodeId> = tree.nodes.iter().map(|n| n.id).collect();
+            assert_eq!(ids, (0..10).map(NodeId).collect::<Vec<_>>(), "{}", name);
+
+            let mut edges: Vec<_> = tree.edges.iter().map(|e| (e.from.0, e.to.0, e.kind)).collect();
+            edges.sort_by_key(|&(from, to, _)| (from, to));
+            let parents = [(0, 1), (0, 7), (0, 8), (0, 9), (1, 2), (1, 3), (1, 4), (1, 5), (1, 6)];
+            let expected: Vec<_> = parents.iter().map(|&(from, to)| (from, to, EdgeKind::Requires)).collect();
+            assert_eq!(edges, expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn migration_drops_stored_progress_and_stamps_the_version() {
+        let mut doc = serde_json::json!({
+            "title": "Old",
+            "progress": 50.0,
+            "nodes": [
+                { "title": "a", "progress": 1.0, "is_lit": true, "tasks": null, "parent": -1 },
+                { "title": "b", "tasks": [], "parent": 0 },
+            ],
+        });
+        migrate(&mut doc).unwrap();
+        assert_eq!(version_of(&doc), CURRENT_VERSION);
+        assert!(doc.get("progress").is_none());
+        assert_eq!(doc["nodes"][0], serde_json::json!({ "title": "a", "id": 0, "tasks": [] }));
+        assert_eq!(doc["edges"], serde_json::json!([{ "from": 0, "to": 1, "kind": "requires" }]));
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let mut doc = serde_json::json!({ "version": CURRENT_VERSION + 1, "title": "Future", "nodes": [] });
+        match migrate(&mut doc) {
+            Err(Error::UnsupportedVersion { found, supported }) => {
+                assert_eq!((found, supported), (CURRENT_VERSION + 1, CURRENT_VERSION));
+            }
+            other => panic!("expected UnsupportedVersion, got {:?}", other),
+        }
+    }
+}
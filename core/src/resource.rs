@@ -0,0 +1,248 @@
+//! Reading material and references attached to nodes.
+//!
+//! Local files are stored relative to the directory of the save file, so a tree
+//! can be moved or shared together with its attachments.
+
+use crate::skill_tree::GTree;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    Article,
+    Book,
+    Video,
+    Course,
+    Repo,
+    Docs,
+    #[default]
+    Other,
+}
+
+impl ResourceKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ResourceKind::Article => "article",
+            ResourceKind::Book => "book",
+            ResourceKind::Video => "video",
+            ResourceKind::Course => "course",
+            ResourceKind::Repo => "repo",
+            ResourceKind::Docs => "docs",
+            ResourceKind::Other => "other",
+        }
+    }
+}
+
+/// Where a resource lives. Saved as `"url": ...` or `"path": ...`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    Url(String),
+    /// Relative to the save file's directory, unless the tree hasn't been saved yet.
+    Path(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resource {
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub kind: ResourceKind,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+impl Resource {
+    pub fn url(label: impl Into<String>, kind: ResourceKind, url: impl Into<String>) -> Self {
+        Resource { label: label.into(), kind, target: Target::Url(url.into()) }
+    }
+
+    /// A local file, made relative to `save_file` when the tree has one.
+    /// The label defaults to the file name.
+    pub fn file(path: &Path, save_file: Option<&Path>) -> Self {
+        let label = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let path = match save_file.and_then(base_dir) {
+            Some(base) => relative_to(path, &base),
+            None => path.to_path_buf(),
+        };
+        Resource { label, kind: ResourceKind::Other, target: Target::Path(path) }
+    }
+
+    /// The label, or the target itself when there is none.
+    pub fn display_name(&self) -> String {
+        if !self.label.trim().is_empty() {
+            return self.label.clone();
+        }
+        match &self.target {
+            Target::Url(url) => url.clone(),
+            Target::Path(path) => path.display().to_string(),
+        }
+    }
+
+    /// What to hand to the system's opener: the url, or the path resolved against `save_file`.
+    pub fn location(&self, save_file: Option<&Path>) -> String {
+        match &self.target {
+            Target::Url(url) => url.clone(),
+            Target::Path(path) => match save_file.and_then(base_dir) {
+                Some(base) => base.join(path).display().to_string(),
+                None => path.display().to_string(),
+            },
+        }
+    }
+}
+
+/// The directory a save file's relative paths start from.
+fn base_dir(save_file: &Path) -> Option<PathBuf> {
+    let dir = save_file.parent()?;
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    Some(std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf()))
+}
+
+/// `path` relative to the directory `base`, climbing out with `..` as needed.
+/// Falls back to the absolute path when the two share no root, e.g. on different drives.
+pub fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let (Ok(path), Ok(base)) = (std::path::absolute(path), std::path::absolute(base)) else {
+        return path.to_path_buf();
+    };
+    let (path, base) = (normalize(&path), normalize(&base));
+    if path.first() != base.first() {
+        return path.iter().collect();
+    }
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut out = PathBuf::new();
+    for _ in common..base.len() {
+        out.push("..");
+    }
+    out.extend(&path[common..]);
+    out
+}
+
+/// The components of an absolute path with `.` and `..` resolved, without touching the file system.
+fn normalize(path: &Path) -> Vec<Component<'_>> {
+    let mut out: Vec<Component> = Vec::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if matches!(out.last(), Some(Component::Normal(_))) {
+                    out.pop();
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+impl GTree {
+    /// Makes attached file paths absolute, for a tree that was saved as `save_file` and is
+    /// going somewhere that hasn't been saved yet.
+    pub fn resolve_resources(&mut self, save_file: &Path) {
+        let Some(base) = base_dir(save_file) else { return };
+        for node in &mut self.nodes {
+            for resource in &mut node.resources {
+                if let Target::Path(path) = &mut resource.target {
+                    *path = normalize(&base.join(&*path)).iter().collect();
+                }
+            }
+        }
+    }
+
+    /// Rewrites attached file paths for a tree that moves from `old` to `new_file`.
+    /// `old` is `None` for a tree that was never saved, whose paths are still as attached.
+    pub fn rebase_resources(&mut self, old: Option<&Path>, new_file: &Path) {
+        let Some(new_base) = base_dir(new_file) else { return };
+        let old_base = old.and_then(base_dir);
+        for node in &mut self.nodes {
+            for resource in &mut node.resources {
+                if let Target::Path(path) = &mut resource.target {
+                    let absolute = match &old_base {
+                        Some(base) => base.join(&*path),
+                        None => path.clone(),
+                    };
+                    *path = relative_to(&absolute, &new_base);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill_tree::GNode;
+
+    fn tree_with_file(path: &str) -> GTree {
+        let mut tree = GTree::new("Tree");
+        let mut node = GNode::new("Node");
+        let target = Target::Path(path.into());
+        node.resources.push(Resource { label: String::new(), kind: ResourceKind::Other, target });
+        tree.add_node(node);
+        tree
+    }
+
+    fn file_of(tree: &GTree) -> &Path {
+        match &tree.nodes[0].resources[0].target {
+            Target::Path(path) => path,
+            Target::Url(url) => panic!("not a file: {}", url),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn relative_paths_climb_out_with_dot_dot() {
+        let relative = |path: &str, base: &str| relative_to(Path::new(path), Path::new(base));
+        assert_eq!(relative("/home/me/trees/notes/a.md", "/home/me/trees"), Path::new("notes/a.md"));
+        assert_eq!(relative("/home/me/docs/a.md", "/home/me/trees/rust"), Path::new("../../docs/a.md"));
+        // Only the root in common.
+        assert_eq!(relative("/srv/a.md", "/home/me"), Path::new("../../srv/a.md"));
+        // `.` and `..` inside either path are resolved first.
+        assert_eq!(relative("/home/me/./trees/../docs/a.md", "/home/me/trees/."), Path::new("../docs/a.md"));
+        assert_eq!(relative("/home/me", "/home/me"), Path::new(""));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn paths_on_another_drive_stay_absolute() {
+        assert_eq!(relative_to(Path::new(r"D:\docs\a.md"), Path::new(r"C:\trees")), Path::new(r"D:\docs\a.md"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn normalize_resolves_dots_without_leaving_the_root() {
+        let normalized = |path: &str| normalize(Path::new(path)).iter().collect::<PathBuf>();
+        assert_eq!(normalized("/a/./b/../c"), Path::new("/a/c"));
+        assert_eq!(normalized("/../../a"), Path::new("/a"));
+        assert_eq!(normalized("/a/b/.."), Path::new("/a"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rebasing_follows_the_save_file() {
+        // Moving from /home/me/trees/rust.json to /home/me/backup/old/rust.json.
+        let mut tree = tree_with_file("notes/a.md");
+        tree.rebase_resources(Some(Path::new("/home/me/trees/rust.json")), Path::new("/home/me/backup/old/rust.json"));
+        assert_eq!(file_of(&tree), Path::new("../../trees/notes/a.md"));
+
+        // Never saved: the path is still as attached, absolute.
+        let mut tree = tree_with_file("/home/me/notes/a.md");
+        tree.rebase_resources(None, Path::new("/home/me/trees/rust.json"));
+        assert_eq!(file_of(&tree), Path::new("../notes/a.md"));
+
+        let mut tree = tree_with_file("../notes/a.md");
+        tree.resolve_resources(Path::new("/home/me/trees/rust.json"));
+        assert_eq!(file_of(&tree), Path::new("/home/me/notes/a.md"));
+    }
+
+    #[test]
+    fn urls_are_left_alone() {
+        let mut tree = GTree::new("Tree");
+        let mut node = GNode::new("Node");
+        node.resources.push(Resource::url("Docs", ResourceKind::Docs, "https://example.com/a"));
+        tree.add_node(node);
+        tree.rebase_resources(Some(Path::new("a/tree.json")), Path::new("b/c/tree.json"));
+        tree.resolve_resources(Path::new("a/tree.json"));
+        assert_eq!(tree.nodes[0].resources[0].target, Target::Url("https://example.com/a".to_string()));
+    }
+}
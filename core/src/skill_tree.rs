@@ -1,36 +1,1133 @@
+//! The skill tree model.
+//!
+//! Progress is never stored: it is derived from task state whenever it is asked
+//! for, and every progress value in this crate is a fraction from 0.0 (nothing
+//! done) to 1.0 (everything done). Front-ends scale it for display themselves.
+
+use crate::achievement::Unlock;
+use crate::activity::{Event, EventKind};
+use crate::error::Error;
+use crate::habit::Habit;
+use crate::migrate;
+use crate::resource::Resource;
+use crate::tags::Rgb;
+use crate::validate::Severity;
+use chrono::{NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Task {
     pub content: String,
+    /// For measured tasks and habits this mirrors whether the target has been reached.
     pub checked: bool,
+    #[serde(default, skip_serializing_if = "TaskKind::is_check")]
+    pub kind: TaskKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<NaiveDate>,
+    /// How much the task counts towards its node, relative to its siblings.
+    #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+    pub weight: f32,
+    /// Expected effort in hours; see `forecast`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<f32>,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+fn is_default_weight(weight: &f32) -> bool {
+    *weight == 1.0
+}
+
+/// Mean of `(weight, progress)` pairs. When nothing carries weight every item counts the same,
+/// so a node made only of zero-weight tasks can still be completed.
+pub(crate) fn weighted_progress(items: impl Iterator<Item = (f32, f32)>) -> f32 {
+    let (mut weighted, mut total, mut plain, mut count) = (0.0, 0.0, 0.0, 0);
+    for (weight, progress) in items {
+        weighted += weight * progress;
+        total += weight;
+        plain += progress;
+        count += 1;
+    }
+    if total > 0.0 {
+        weighted / total
+    } else if count > 0 {
+        plain / count as f32
+    } else {
+        0.0
+    }
+}
+
+impl Task {
+    pub fn new(content: impl Into<String>) -> Self {
+        Task { content: content.into(), checked: false, kind: TaskKind::Check, due: None, weight: default_weight(), estimate: None }
+    }
+
+    pub fn progress(&self) -> f32 {
+        match &self.kind {
+            TaskKind::Check => {
+                if self.checked { 1.0 } else { 0.0 }
+            }
+            TaskKind::Measure(m) => m.fraction(),
+            TaskKind::Habit(h) => h.progress(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskKind {
+    /// A plain checkbox.
+    #[default]
+    Check,
+    /// A numeric target like "Reach 35 WPM"; partial values count as partial progress.
+    Measure(Measure),
+    /// Recurring practice like "Practice touch typing daily", done once its target streak is reached.
+    Habit(Habit),
+}
+
+impl TaskKind {
+    pub fn is_check(&self) -> bool {
+        *self == TaskKind::Check
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Done once `current` has climbed to `target` (words per minute, pages read).
+    #[default]
+    Increase,
+    /// Done once `current` has dropped to `target` (lap time, body weight).
+    Decrease,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Measure {
+    pub unit: String,
+    /// Where the measurement started; progress is counted from here.
+    #[serde(default)]
+    pub start: f32,
+    pub target: f32,
+    pub current: f32,
+    #[serde(default)]
+    pub direction: Direction,
+}
+
+impl Measure {
+    /// How far `current` has moved from `start` towards `target`, clamped to 0..1.
+    pub fn fraction(&self) -> f32 {
+        let (done, span) = match self.direction {
+            Direction::Increase => (self.current - self.start, self.target - self.start),
+            Direction::Decrease => (self.start - self.current, self.start - self.target),
+        };
+        if span <= 0.0 {
+            return if self.is_reached() { 1.0 } else { 0.0 };
+        }
+        (done / span).clamp(0.0, 1.0)
+    }
+
+    pub fn is_reached(&self) -> bool {
+        match self.direction {
+            Direction::Increase => self.current >= self.target,
+            Direction::Decrease => self.current <= self.target,
+        }
+    }
+}
+
+/// Stable identifier of a node. Unlike a position in `GTree::nodes` it survives
+/// deleting and reordering nodes, so it is what edges and front-ends refer to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct NodeId(pub u32);
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Something a node is meant to achieve, as opposed to a task that gets it done.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "GoalRepr")]
+pub struct Goal {
+    pub text: String,
+    pub done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Goal {
+    pub fn new(text: impl Into<String>) -> Self {
+        Goal { text: text.into(), done: false, notes: None }
+    }
+}
+
+/// Older files list goals as bare strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GoalRepr {
+    Text(String),
+    Full {
+        text: String,
+        #[serde(default)]
+        done: bool,
+        #[serde(default)]
+        notes: Option<String>,
+    },
+}
+
+impl From<GoalRepr> for Goal {
+    fn from(repr: GoalRepr) -> Self {
+        match repr {
+            GoalRepr::Text(text) => Goal::new(text),
+            GoalRepr::Full { text, done, notes } => Goal { text, done, notes },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GNode {
+    pub id: NodeId,
     pub title: String,
+    #[serde(default)]
     pub description: String,
-    pub progress: f32,
     pub tasks: Vec<Task>,
-    pub is_lit: bool,
+    #[serde(default)]
+    pub goals: Vec<Goal>,
+    /// Position of the node's centre in world space, used by the graph view.
+    #[serde(default)]
+    pub x: f32,
+    #[serde(default)]
+    pub y: f32,
+    /// Radius of the node in world space.
+    #[serde(default = "default_radius")]
+    pub r: f32,
+    /// When the node should be complete.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_date: Option<NaiveDate>,
+    /// How much the node counts towards tree and branch progress, e.g. its effort in points.
+    #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+    pub weight: f32,
+    /// Free-form labels such as "rust" or "tooling"; see `tags::TagFilter`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// The area of skills the node belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Overrides the colour front-ends pick for the node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Rgb>,
+    /// Reading material and references for the skill.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resources: Vec<Resource>,
+    /// Expected effort in hours for the whole node. Tasks without their own estimate share
+    /// what their siblings' estimates leave of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<f32>,
+}
+
+fn default_radius() -> f32 {
+    30.0
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl GNode {
+    /// A blank node at the origin. Its id is assigned by `GTree::add_node`.
+    pub fn new(title: impl Into<String>) -> Self {
+        GNode {
+            id: NodeId(0),
+            title: title.into(),
+            description: String::new(),
+            tasks: Vec::new(),
+            goals: Vec::new(),
+            x: 0.0,
+            y: 0.0,
+            r: default_radius(),
+            target_date: None,
+            weight: default_weight(),
+            tags: Vec::new(),
+            category: None,
+            color: None,
+            resources: Vec::new(),
+            estimate: None,
+        }
+    }
+
+    /// Weighted share of finished tasks. A node without tasks has nothing done yet and sits at 0.0.
+    pub fn progress(&self) -> f32 {
+        weighted_progress(self.tasks.iter().map(|t| (t.weight, t.progress())))
+    }
+
+    /// A node is lit once all of its work is done.
+    pub fn is_lit(&self) -> bool {
+        self.progress() >= 1.0
+    }
+}
+
+/// How a dependent node relates to one of its prerequisites.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// The prerequisite has to be completed before the dependent can be worked on.
+    Requires,
+    /// The prerequisite is suggested reading but does not gate the dependent.
+    Recommends,
+}
+
+/// A directed prerequisite edge: `from` has to come before `to`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeError {
+    UnknownNode(NodeId),
+    SelfLoop(NodeId),
+    Duplicate { from: NodeId, to: NodeId },
+    Cycle { from: NodeId, to: NodeId },
+}
+
+impl fmt::Display for EdgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EdgeError::UnknownNode(n) => write!(f, "node {} does not exist", n),
+            EdgeError::SelfLoop(n) => write!(f, "node {} cannot be its own prerequisite", n),
+            EdgeError::Duplicate { from, to } => write!(f, "edge {} -> {} already exists", from, to),
+            EdgeError::Cycle { from, to } => write!(f, "edge {} -> {} would create a cycle", from, to),
+        }
+    }
+}
+
+impl std::error::Error for EdgeError {}
+
+/// What `GTree::remove_node_with` does with the dependents of the removed node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovePolicy {
+    /// Dependents just lose the edge; those without other prerequisites become roots.
+    Detach,
+    /// Dependents take over the removed node's prerequisites.
+    Rehome,
+    /// The node goes together with every dependent that requires nothing but doomed nodes.
+    /// `Recommends` links don't pull anything along.
+    Cascade,
+}
+
+/// Where a node stands, derived from its prerequisites and its own tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeState {
+    /// At least one `Requires` prerequisite is not complete yet.
+    Locked,
+    /// Unlocked, nothing done yet.
+    Available,
+    /// Unlocked and partly done.
+    InProgress,
+    /// Unlocked and all tasks done.
+    Complete,
+}
+
+/// The front-end modes from the README. Edit mode changes the tree freely,
+/// grind mode only lets you work through unlocked nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Edit,
+    Grind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskError {
+    UnknownNode(NodeId),
+    UnknownTask { node: NodeId, index: usize },
+    /// Changing a task through the setter of another kind, e.g. checking a habit.
+    WrongKind { node: NodeId, index: usize },
+    Locked(NodeId),
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskError::UnknownNode(n) => write!(f, "node {} does not exist", n),
+            TaskError::UnknownTask { node, index } => write!(f, "node {} has no task {}", node, index),
+            TaskError::WrongKind { node, index } => {
+                write!(f, "task {} of node {} can't be changed that way", index, node)
+            }
+            TaskError::Locked(n) => write!(f, "node {} is locked until its prerequisites are complete", n),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GTree {
     pub title: String,
-    pub progress: f32,
     pub nodes: Vec<GNode>,
+    #[serde(default)]
+    pub edges: Vec<Edge>,
+    /// Append-only; read it through `activity()`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    activity: Vec<Event>,
+    /// Awarded achievements, oldest first; read it through `unlocked()`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    achievements: Vec<Unlock>,
+}
+
+impl GTree {
+    pub fn new(title: impl Into<String>) -> Self {
+        GTree { title: title.into(), nodes: Vec::new(), edges: Vec::new(), activity: Vec::new(), achievements: Vec::new() }
+    }
+
+    /// Progress over all nodes, weighted by node weight.
+    pub fn progress(&self) -> f32 {
+        self.average_progress(self.nodes.iter().map(|n| n.id))
+    }
+
+    /// Progress of the branch rooted at `root`: the node plus everything that builds on it.
+    pub fn subtree_progress(&self, root: NodeId) -> f32 {
+        self.average_progress(self.subtree(root))
+    }
+
+    /// Progress of `node` together with everything it transitively needs.
+    pub fn prerequisite_progress(&self, node: NodeId) -> f32 {
+        self.average_progress(self.prerequisite_closure(node))
+    }
+
+    /// The subgoals the total splits into: every node directly unlocked by a root.
+    /// A tree made only of roots has no structure to split on, so its roots are the branches.
+    pub fn branches(&self) -> Vec<NodeId> {
+        let roots = self.roots();
+        let mut branches: Vec<NodeId> = Vec::new();
+        for &root in &roots {
+            for e in self.dependents(root) {
+                if !branches.contains(&e.to) {
+                    branches.push(e.to);
+                }
+            }
+        }
+        if branches.is_empty() { roots } else { branches }
+    }
+
+    fn average_progress(&self, ids: impl IntoIterator<Item = NodeId>) -> f32 {
+        weighted_progress(
+            ids.into_iter()
+                .filter_map(|id| self.node(id))
+                .map(|n| (n.weight, n.progress())),
+        )
+    }
+
+    /// The state of a single node. Use `node_states` when you need all of them.
+    pub fn node_state(&self, id: NodeId) -> NodeState {
+        let mut states = HashMap::new();
+        self.resolve_state(id, &mut states)
+    }
+
+    pub fn node_states(&self) -> HashMap<NodeId, NodeState> {
+        let mut states = HashMap::new();
+        for node in &self.nodes {
+            self.resolve_state(node.id, &mut states);
+        }
+        states
+    }
+
+    fn resolve_state(&self, id: NodeId, states: &mut HashMap<NodeId, NodeState>) -> NodeState {
+        if let Some(&state) = states.get(&id) {
+            return state;
+        }
+        // Provisional entry so that a cycle in a broken file ends up locked instead of recursing forever.
+        states.insert(id, NodeState::Locked);
+        let requires: Vec<NodeId> = self
+            .prerequisites(id)
+            .filter(|e| e.kind == EdgeKind::Requires)
+            .map(|e| e.from)
+            .collect();
+        let unlocked = requires
+            .into_iter()
+            .all(|p| self.resolve_state(p, states) == NodeState::Complete);
+        let progress = self.node(id).map_or(0.0, GNode::progress);
+        let state = if !unlocked {
+            NodeState::Locked
+        } else if progress >= 1.0 {
+            NodeState::Complete
+        } else if progress > 0.0 {
+            NodeState::InProgress
+        } else {
+            NodeState::Available
+        };
+        states.insert(id, state);
+        state
+    }
+
+    /// Checks or unchecks a task. In grind mode tasks of locked nodes can't be touched.
+    pub fn set_task_checked(&mut self, mode: Mode, node: NodeId, task: usize, checked: bool) -> Result<(), TaskError> {
+        if mode == Mode::Grind && self.node_state(node) == NodeState::Locked {
+            return Err(TaskError::Locked(node));
+        }
+        let t = self.task_mut(node, task)?;
+        if !t.kind.is_check() {
+            return Err(TaskError::WrongKind { node, index: task });
+        }
+        if t.checked != checked {
+            t.checked = checked;
+            self.record(if checked {
+                EventKind::TaskChecked { node, task }
+            } else {
+                EventKind::TaskUnchecked { node, task }
+            });
+        }
+        Ok(())
+    }
+
+    /// Records a new reading for a measured task. Same locking rules as `set_task_checked`.
+    pub fn set_task_value(&mut self, mode: Mode, node: NodeId, task: usize, value: f32) -> Result<(), TaskError> {
+        if mode == Mode::Grind && self.node_state(node) == NodeState::Locked {
+            return Err(TaskError::Locked(node));
+        }
+        let t = self.task_mut(node, task)?;
+        let TaskKind::Measure(m) = &mut t.kind else {
+            return Err(TaskError::WrongKind { node, index: task });
+        };
+        m.current = value;
+        t.checked = m.is_reached();
+        self.record(EventKind::TaskValueSet { node, task, value });
+        Ok(())
+    }
+
+    /// Marks a habit as done (or not) on `day`. Same locking rules as `set_task_checked`.
+    pub fn set_habit_done(
+        &mut self,
+        mode: Mode,
+        node: NodeId,
+        task: usize,
+        day: NaiveDate,
+        done: bool,
+    ) -> Result<(), TaskError> {
+        if mode == Mode::Grind && self.node_state(node) == NodeState::Locked {
+            return Err(TaskError::Locked(node));
+        }
+        let t = self.task_mut(node, task)?;
+        let TaskKind::Habit(h) = &mut t.kind else {
+            return Err(TaskError::WrongKind { node, index: task });
+        };
+        if h.done_on(day) != done {
+            h.set_done(day, done);
+            t.checked = h.progress() >= 1.0;
+            self.record(if done {
+                EventKind::HabitDone { node, task, day }
+            } else {
+                EventKind::HabitUndone { node, task, day }
+            });
+        }
+        Ok(())
+    }
+
+    /// Habits on unlocked nodes that still want doing on `today`.
+    pub fn due_habits(&self, today: NaiveDate) -> Vec<(NodeId, usize)> {
+        self.due_habits_in(today, &self.node_states())
+    }
+
+    /// `due_habits` with the node states given, e.g. `Workspace::tree_states`.
+    pub fn due_habits_in(&self, today: NaiveDate, states: &HashMap<NodeId, NodeState>) -> Vec<(NodeId, usize)> {
+        let mut due = Vec::new();
+        for node in &self.nodes {
+            if states.get(&node.id) == Some(&NodeState::Locked) {
+                continue;
+            }
+            for (i, task) in node.tasks.iter().enumerate() {
+                if let TaskKind::Habit(h) = &task.kind
+                    && h.is_due(today)
+                {
+                    due.push((node.id, i));
+                }
+            }
+        }
+        due
+    }
+
+    fn task_mut(&mut self, node: NodeId, task: usize) -> Result<&mut Task, TaskError> {
+        let n = self.node_mut(node).ok_or(TaskError::UnknownNode(node))?;
+        n.tasks.get_mut(task).ok_or(TaskError::UnknownTask { node, index: task })
+    }
+
+    /// `root` followed by every node reachable from it along outgoing edges.
+    pub fn subtree(&self, root: NodeId) -> Vec<NodeId> {
+        self.reachable(root, |e| e.from, |e| e.to)
+    }
+
+    /// `node` followed by every node it transitively depends on.
+    pub fn prerequisite_closure(&self, node: NodeId) -> Vec<NodeId> {
+        self.reachable(node, |e| e.to, |e| e.from)
+    }
+
+    fn reachable(&self, start: NodeId, near: fn(&Edge) -> NodeId, far: fn(&Edge) -> NodeId) -> Vec<NodeId> {
+        let mut found = vec![start];
+        let mut i = 0;
+        while i < found.len() {
+            let current = found[i];
+            for e in self.edges.iter().filter(|e| near(e) == current) {
+                if !found.contains(&far(e)) {
+                    found.push(far(e));
+                }
+            }
+            i += 1;
+        }
+        found
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&GNode> {
+        self.nodes.iter().find(|n| n.id == id)
+    }
+
+    /// Direct access for in-place fixes; changes made through it are not logged. Prefer `edit_node`.
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut GNode> {
+        self.nodes.iter_mut().find(|n| n.id == id)
+    }
+
+    /// Changes a node and logs that it was edited.
+    pub fn edit_node<R>(&mut self, id: NodeId, edit: impl FnOnce(&mut GNode) -> R) -> Option<R> {
+        let result = edit(self.node_mut(id)?);
+        self.record(EventKind::NodeEdited { node: id });
+        Some(result)
+    }
+
+    /// Everything that happened to the tree, oldest first.
+    pub fn activity(&self) -> &[Event] {
+        &self.activity
+    }
+
+    /// Achievements awarded so far, oldest first.
+    pub fn unlocked(&self) -> &[Unlock] {
+        &self.achievements
+    }
+
+    pub(crate) fn unlock(&mut self, unlock: Unlock) {
+        self.achievements.push(unlock);
+    }
+
+    /// Puts every task, habit and goal back to where it started and forgets the activity log
+    /// and the achievements. Only for turning a tree into a template's fresh copy: anywhere
+    /// else the log is append-only.
+    pub(crate) fn reset_progress(&mut self) {
+        for node in &mut self.nodes {
+            for task in &mut node.tasks {
+                task.checked = false;
+                match &mut task.kind {
+                    TaskKind::Check => {}
+                    TaskKind::Measure(m) => m.current = m.start,
+                    TaskKind::Habit(h) => h.log.clear(),
+                }
+            }
+            for goal in &mut node.goals {
+                goal.done = false;
+            }
+        }
+        self.activity.clear();
+        self.achievements.clear();
+    }
+
+    pub(crate) fn record(&mut self, kind: EventKind) {
+        self.activity.push(Event { at: Utc::now(), kind });
+    }
+
+    pub fn index_of(&self, id: NodeId) -> Option<usize> {
+        self.nodes.iter().position(|n| n.id == id)
+    }
+
+    /// An id that no node in the tree uses yet, nor any removed node the activity log mentions.
+    pub fn next_id(&self) -> NodeId {
+        let live = self.nodes.iter().map(|n| n.id);
+        let logged = self.activity.iter().map(|e| e.kind.node());
+        NodeId(live.chain(logged).map(|id| id.0 + 1).max().unwrap_or(0))
+    }
+
+    /// Appends `node` under a fresh id, ignoring whatever id it carried.
+    pub fn add_node(&mut self, mut node: GNode) -> NodeId {
+        node.id = self.next_id();
+        let id = node.id;
+        self.nodes.push(node);
+        self.record(EventKind::NodeAdded { node: id });
+        id
+    }
+
+    /// Removes a node together with every edge touching it; its dependents just lose that prerequisite.
+    pub fn remove_node(&mut self, id: NodeId) -> Option<GNode> {
+        let i = self.index_of(id)?;
+        self.edges.retain(|e| e.from != id && e.to != id);
+        self.record(EventKind::NodeRemoved { node: id });
+        Some(self.nodes.remove(i))
+    }
+
+    /// Removes a node, deciding with `policy` what becomes of the nodes that depend on it.
+    /// Returns the removed nodes, the requested one first.
+    pub fn remove_node_with(&mut self, id: NodeId, policy: RemovePolicy) -> Vec<GNode> {
+        if self.node(id).is_none() {
+            return Vec::new();
+        }
+        match policy {
+            RemovePolicy::Detach => {}
+            RemovePolicy::Rehome => {
+                let incoming: Vec<Edge> = self.prerequisites(id).copied().collect();
+                let outgoing: Vec<Edge> = self.dependents(id).copied().collect();
+                for p in &incoming {
+                    for c in &outgoing {
+                        // p -> id -> c already ordered p before c, so this can't close a cycle.
+                        if p.from == c.to {
+                            continue;
+                        }
+                        let kind = if p.kind == EdgeKind::Requires && c.kind == EdgeKind::Requires {
+                            EdgeKind::Requires
+                        } else {
+                            EdgeKind::Recommends
+                        };
+                        // An existing soft link is tightened rather than letting the gate go.
+                        match self.edges.iter_mut().find(|e| e.from == p.from && e.to == c.to) {
+                            Some(existing) if kind == EdgeKind::Requires => existing.kind = kind,
+                            Some(_) => {}
+                            None => self.edges.push(Edge { from: p.from, to: c.to, kind }),
+                        }
+                    }
+                }
+            }
+            RemovePolicy::Cascade => {
+                let doomed = self.cascade(id);
+                return doomed.into_iter().filter_map(|n| self.remove_node(n)).collect();
+            }
+        }
+        self.remove_node(id).into_iter().collect()
+    }
+
+    /// `id` followed by the nodes that only stand on it: each has a `Requires` prerequisite
+    /// among the earlier ones and no `Requires` prerequisite elsewhere.
+    fn cascade(&self, id: NodeId) -> Vec<NodeId> {
+        let mut doomed = vec![id];
+        loop {
+            let next = self.nodes.iter().map(|n| n.id).find(|&n| {
+                let mut required = self.prerequisites(n).filter(|e| e.kind == EdgeKind::Requires).peekable();
+                !doomed.contains(&n) && required.peek().is_some() && required.all(|e| doomed.contains(&e.from))
+            });
+            match next {
+                Some(n) => doomed.push(n),
+                None => return doomed,
+            }
+        }
+    }
+
+    /// Adds `node` as a new node that requires `parent`.
+    pub fn add_child(&mut self, parent: NodeId, node: GNode) -> Result<NodeId, EdgeError> {
+        if self.node(parent).is_none() {
+            return Err(EdgeError::UnknownNode(parent));
+        }
+        let id = self.add_node(node);
+        self.add_edge(parent, id, EdgeKind::Requires)?;
+        Ok(id)
+    }
+
+    /// Replaces every `Requires` prerequisite of `node` with `new_parent`, or makes it a root
+    /// when `new_parent` is `None`. `Recommends` edges are kept. Nothing changes on error.
+    pub fn reparent(&mut self, node: NodeId, new_parent: Option<NodeId>) -> Result<(), EdgeError> {
+        if self.node(node).is_none() {
+            return Err(EdgeError::UnknownNode(node));
+        }
+        if let Some(parent) = new_parent {
+            if self.node(parent).is_none() {
+                return Err(EdgeError::UnknownNode(parent));
+            }
+            if parent == node {
+                return Err(EdgeError::SelfLoop(node));
+            }
+            // Dropping node's own prerequisites can't break a path from node down to parent,
+            // so this check is the same before and after.
+            if self.depends_on(parent, node) {
+                return Err(EdgeError::Cycle { from: parent, to: node });
+            }
+        }
+        self.edges.retain(|e| !(e.to == node && e.kind == EdgeKind::Requires));
+        if let Some(parent) = new_parent {
+            self.edges.retain(|e| !(e.from == parent && e.to == node));
+            self.edges.push(Edge { from: parent, to: node, kind: EdgeKind::Requires });
+        }
+        self.record(EventKind::NodeEdited { node });
+        Ok(())
+    }
+
+    /// Moves the task at index `from` of `node` to index `to`, shifting the ones in between.
+    pub fn move_task(&mut self, node: NodeId, from: usize, to: usize) -> Result<(), TaskError> {
+        let n = self.node_mut(node).ok_or(TaskError::UnknownNode(node))?;
+        for index in [from, to] {
+            if index >= n.tasks.len() {
+                return Err(TaskError::UnknownTask { node, index });
+            }
+        }
+        let task = n.tasks.remove(from);
+        n.tasks.insert(to, task);
+        self.record(EventKind::NodeEdited { node });
+        Ok(())
+    }
+
+    /// Adds a prerequisite edge, rejecting anything that would break the DAG.
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId, kind: EdgeKind) -> Result<(), EdgeError> {
+        for n in [from, to] {
+            if self.node(n).is_none() {
+                return Err(EdgeError::UnknownNode(n));
+            }
+        }
+        if from == to {
+            return Err(EdgeError::SelfLoop(from));
+        }
+        if self.edge(from, to).is_some() {
+            return Err(EdgeError::Duplicate { from, to });
+        }
+        if self.depends_on(from, to) {
+            return Err(EdgeError::Cycle { from, to });
+        }
+        self.edges.push(Edge { from, to, kind });
+        Ok(())
+    }
+
+    pub fn remove_edge(&mut self, from: NodeId, to: NodeId) -> Option<Edge> {
+        let i = self.edges.iter().position(|e| e.from == from && e.to == to)?;
+        Some(self.edges.remove(i))
+    }
+
+    pub fn edge(&self, from: NodeId, to: NodeId) -> Option<&Edge> {
+        self.edges.iter().find(|e| e.from == from && e.to == to)
+    }
+
+    /// Edges pointing into `node`, i.e. the things it needs.
+    pub fn prerequisites(&self, node: NodeId) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |e| e.to == node)
+    }
+
+    /// Edges leaving `node`, i.e. the things it unlocks.
+    pub fn dependents(&self, node: NodeId) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |e| e.from == node)
+    }
+
+    /// The nodes `node` hangs off in the tree view, i.e. its prerequisites.
+    pub fn parents(&self, node: NodeId) -> impl Iterator<Item = &GNode> {
+        self.prerequisites(node).filter_map(|e| self.node(e.from))
+    }
+
+    /// The nodes hanging off `node` in the tree view, i.e. what it unlocks.
+    pub fn children(&self, node: NodeId) -> impl Iterator<Item = &GNode> {
+        self.dependents(node).filter_map(|e| self.node(e.to))
+    }
+
+    /// Nodes without any prerequisites.
+    pub fn roots(&self) -> Vec<NodeId> {
+        self.nodes
+            .iter()
+            .map(|n| n.id)
+            .filter(|&n| self.prerequisites(n).next().is_none())
+            .collect()
+    }
+
+    /// True if `node` transitively needs `prerequisite`.
+    pub fn depends_on(&self, node: NodeId, prerequisite: NodeId) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack = vec![node];
+        while let Some(n) = stack.pop() {
+            for e in self.prerequisites(n) {
+                if e.from == prerequisite {
+                    return true;
+                }
+                if seen.insert(e.from) {
+                    stack.push(e.from);
+                }
+            }
+        }
+        false
+    }
+}
+
+/// What actually goes on disk: the tree plus the schema version it was written with.
+#[derive(Serialize)]
+struct Document<'a> {
+    version: u64,
+    #[serde(flatten)]
+    tree: &'a GTree,
 }
 
-pub fn save_tree_to_file(tree: &GTree, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let json_str = serde_json::to_string_pretty(tree)?;
+pub fn save_tree_to_file(tree: &GTree, path: &str) -> Result<(), Error> {
+    let doc = Document { version: migrate::CURRENT_VERSION, tree };
+    let json_str = serde_json::to_string_pretty(&doc)?;
     fs::write(path, json_str)?;
     Ok(())
 }
 
-pub fn load_tree_from_file(path: &str) -> Result<GTree, Box<dyn std::error::Error>> {
+/// Loads a tree written by any version of the app, upgrading it on the way in.
+/// Trees that fail validation with errors are rejected; warnings are left to the caller.
+pub fn load_tree_from_file(path: &str) -> Result<GTree, Error> {
     let json_str = fs::read_to_string(path)?;
-    let tree: GTree = serde_json::from_str(&json_str)?;
+    let mut value: serde_json::Value = serde_json::from_str(&json_str)?;
+    let tree: GTree = if migrate::version_of(&value) == migrate::CURRENT_VERSION {
+        // Parse the text again rather than the value so shape errors keep their line and column.
+        serde_json::from_str(&json_str)?
+    } else {
+        migrate::migrate(&mut value)?;
+        serde_json::from_value(value)?
+    };
+    let errors: Vec<_> = tree
+        .validate()
+        .into_iter()
+        .filter(|d| d.severity == Severity::Error)
+        .collect();
+    if !errors.is_empty() {
+        return Err(Error::Invalid(errors));
+    }
     Ok(tree)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a -> b -> c, plus a -> d.
+    fn sample() -> (GTree, [NodeId; 4]) {
+        let mut tree = GTree::new("sample");
+        let a = tree.add_node(GNode::new("a"));
+        let b = tree.add_child(a, GNode::new("b")).unwrap();
+        let c = tree.add_child(b, GNode::new("c")).unwrap();
+        let d = tree.add_child(a, GNode::new("d")).unwrap();
+        (tree, [a, b, c, d])
+    }
+
+    fn parents(tree: &GTree, node: NodeId) -> Vec<NodeId> {
+        tree.parents(node).map(|n| n.id).collect()
+    }
+
+    /// `sample` with a task on a and two on b.
+    fn with_tasks() -> (GTree, [NodeId; 4]) {
+        let (mut tree, ids) = sample();
+        tree.edit_node(ids[0], |n| n.tasks.push(Task::new("a1")));
+        tree.edit_node(ids[1], |n| n.tasks.extend([Task::new("b1"), Task::new("b2")]));
+        (tree, ids)
+    }
+
+    #[test]
+    fn requires_edges_lock_until_prerequisites_complete() {
+        let (mut tree, [a, b, c, d]) = with_tasks();
+        let state = |tree: &GTree, id| tree.node_states()[&id];
+        assert_eq!(state(&tree, a), NodeState::Available);
+        assert_eq!([b, c, d].map(|id| state(&tree, id)), [NodeState::Locked; 3]);
+
+        tree.set_task_checked(Mode::Grind, a, 0, true).unwrap();
+        assert_eq!(state(&tree, a), NodeState::Complete);
+        assert_eq!(state(&tree, b), NodeState::Available);
+        // d has no work at all, so it is never more than available.
+        assert_eq!(state(&tree, d), NodeState::Available);
+        assert_eq!(state(&tree, c), NodeState::Locked);
+
+        tree.set_task_checked(Mode::Grind, b, 0, true).unwrap();
+        assert_eq!(tree.node_state(b), NodeState::InProgress);
+        assert_eq!(tree.node_state(c), NodeState::Locked);
+        tree.set_task_checked(Mode::Grind, b, 1, true).unwrap();
+        assert_eq!(tree.node_state(b), NodeState::Complete);
+        assert_eq!(tree.node_state(c), NodeState::Available);
+    }
+
+    #[test]
+    fn recommends_edges_do_not_lock() {
+        let (mut tree, [a, b, _, _]) = with_tasks();
+        let e = tree.add_node(GNode::new("e"));
+        tree.add_edge(b, e, EdgeKind::Recommends).unwrap();
+        assert_eq!(tree.node_state(e), NodeState::Available);
+        tree.add_edge(a, e, EdgeKind::Requires).unwrap();
+        assert_eq!(tree.node_state(e), NodeState::Locked);
+    }
+
+    #[test]
+    fn grind_mode_refuses_work_on_locked_nodes() {
+        let (mut tree, [_, b, _, _]) = with_tasks();
+        assert_eq!(tree.set_task_checked(Mode::Grind, b, 0, true), Err(TaskError::Locked(b)));
+        assert!(!tree.nodes[1].tasks[0].checked);
+
+        tree.edit_node(b, |n| {
+            n.tasks[0].kind = TaskKind::Measure(Measure {
+                unit: "wpm".to_string(),
+                start: 0.0,
+                target: 60.0,
+                current: 0.0,
+                direction: Direction::Increase,
+            });
+            n.tasks[1].kind = TaskKind::Habit(crate::habit::Habit::new(crate::habit::Schedule::Daily));
+        });
+        let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        assert_eq!(tree.set_task_value(Mode::Grind, b, 0, 30.0), Err(TaskError::Locked(b)));
+        assert_eq!(tree.set_habit_done(Mode::Grind, b, 1, day, true), Err(TaskError::Locked(b)));
+        // Edit mode may fix anything up.
+        tree.set_task_value(Mode::Edit, b, 0, 30.0).unwrap();
+        tree.set_habit_done(Mode::Edit, b, 1, day, true).unwrap();
+    }
+
+    #[test]
+    fn weighted_progress_counts_heavier_items_more() {
+        assert_eq!(weighted_progress([(3.0, 1.0), (1.0, 0.0)].into_iter()), 0.75);
+        assert_eq!(weighted_progress([(1.0, 0.5), (0.0, 0.0), (1.0, 1.0)].into_iter()), 0.75);
+
+        let mut node = GNode::new("Node");
+        node.tasks.extend([Task::new("heavy"), Task::new("light")]);
+        node.tasks[0].weight = 4.0;
+        node.tasks[0].checked = true;
+        assert_eq!(node.progress(), 0.8);
+    }
+
+    #[test]
+    fn weighted_progress_without_weight_is_a_plain_mean() {
+        assert_eq!(weighted_progress([(0.0, 1.0), (0.0, 0.0)].into_iter()), 0.5);
+        assert_eq!(weighted_progress(std::iter::empty()), 0.0);
+
+        let mut node = GNode::new("Node");
+        node.tasks.push(Task::new("weightless"));
+        node.tasks[0].weight = 0.0;
+        assert_eq!(node.progress(), 0.0);
+        node.tasks[0].checked = true;
+        assert!(node.is_lit());
+
+        let mut tree = GTree::new("Tree");
+        for _ in 0..2 {
+            let mut node = GNode::new("Node");
+            node.weight = 0.0;
+            tree.add_node(node);
+        }
+        assert_eq!(tree.progress(), 0.0);
+        assert!(!GTree::new("Empty").progress().is_nan());
+    }
+
+    #[test]
+    fn add_child_links_to_parent() {
+        let (tree, [a, b, _, d]) = sample();
+        assert_eq!(tree.nodes.len(), 4);
+        assert_eq!(parents(&tree, b), vec![a]);
+        assert_eq!(parents(&tree, d), vec![a]);
+        assert!(tree.validate().is_empty());
+    }
+
+    #[test]
+    fn add_child_rejects_unknown_parent() {
+        let (mut tree, _) = sample();
+        let err = tree.add_child(NodeId(99), GNode::new("x")).unwrap_err();
+        assert_eq!(err, EdgeError::UnknownNode(NodeId(99)));
+        assert_eq!(tree.nodes.len(), 4);
+    }
+
+    #[test]
+    fn ids_are_not_reused_after_removal() {
+        let (mut tree, [_, _, _, d]) = sample();
+        tree.remove_node(d);
+        let e = tree.add_node(GNode::new("e"));
+        assert_ne!(e, d);
+    }
+
+    #[test]
+    fn remove_detach_orphans_dependents() {
+        let (mut tree, [_, b, c, _]) = sample();
+        let removed = tree.remove_node_with(b, RemovePolicy::Detach);
+        assert_eq!(removed.len(), 1);
+        assert!(parents(&tree, c).is_empty());
+        assert!(tree.edges.iter().all(|e| e.from != b && e.to != b));
+        assert!(tree.validate().is_empty());
+    }
+
+    #[test]
+    fn remove_rehome_moves_dependents_to_grandparent() {
+        let (mut tree, [a, b, c, _]) = sample();
+        tree.remove_node_with(b, RemovePolicy::Rehome);
+        assert_eq!(parents(&tree, c), vec![a]);
+        assert_eq!(tree.edge(a, c).map(|e| e.kind), Some(EdgeKind::Requires));
+        assert!(tree.validate().is_empty());
+    }
+
+    #[test]
+    fn remove_rehome_tightens_an_existing_soft_link() {
+        let (mut tree, [a, b, c, _]) = sample();
+        tree.add_edge(a, c, EdgeKind::Recommends).unwrap();
+        tree.remove_node_with(b, RemovePolicy::Rehome);
+        assert_eq!(tree.edge(a, c).map(|e| e.kind), Some(EdgeKind::Requires));
+        assert_eq!(tree.edges.iter().filter(|e| e.to == c).count(), 1);
+        assert_eq!(tree.node_state(c), NodeState::Locked);
+        assert!(tree.validate().is_empty());
+    }
+
+    #[test]
+    fn remove_cascade_takes_subtree() {
+        let (mut tree, [a, b, c, d]) = sample();
+        let removed: Vec<NodeId> = tree.remove_node_with(b, RemovePolicy::Cascade).iter().map(|n| n.id).collect();
+        assert_eq!(removed, vec![b, c]);
+        let left: Vec<NodeId> = tree.nodes.iter().map(|n| n.id).collect();
+        assert_eq!(left, vec![a, d]);
+        assert!(tree.validate().is_empty());
+    }
+
+    #[test]
+    fn remove_cascade_keeps_nodes_with_other_requirements() {
+        let mut tree = GTree::new("t");
+        let [a, b, c] = ["a", "b", "c"].map(|t| tree.add_node(GNode::new(t)));
+        tree.add_edge(a, b, EdgeKind::Recommends).unwrap();
+        tree.add_edge(c, b, EdgeKind::Requires).unwrap();
+        let removed: Vec<NodeId> = tree.remove_node_with(a, RemovePolicy::Cascade).iter().map(|n| n.id).collect();
+        assert_eq!(removed, vec![a]);
+
+        let d = tree.add_child(c, GNode::new("d")).unwrap();
+        let e = tree.add_child(d, GNode::new("e")).unwrap();
+        tree.add_edge(b, e, EdgeKind::Requires).unwrap();
+        let removed: Vec<NodeId> = tree.remove_node_with(c, RemovePolicy::Cascade).iter().map(|n| n.id).collect();
+        assert_eq!(removed, vec![c, b, d, e]);
+    }
+
+    #[test]
+    fn reparent_replaces_required_parents() {
+        let (mut tree, [a, b, c, d]) = sample();
+        tree.add_edge(a, c, EdgeKind::Recommends).unwrap();
+        tree.reparent(c, Some(d)).unwrap();
+        assert_eq!(tree.edge(d, c).map(|e| e.kind), Some(EdgeKind::Requires));
+        assert!(tree.edge(b, c).is_none());
+        // soft links survive
+        assert_eq!(tree.edge(a, c).map(|e| e.kind), Some(EdgeKind::Recommends));
+    }
+
+    #[test]
+    fn reparent_to_none_makes_a_root() {
+        let (mut tree, [_, b, c, _]) = sample();
+        tree.reparent(c, None).unwrap();
+        assert!(tree.roots().contains(&c));
+        assert!(tree.edge(b, c).is_none());
+    }
+
+    #[test]
+    fn reparent_rejects_cycles_without_changes() {
+        let (mut tree, [a, b, c, _]) = sample();
+        let before = tree.edges.clone();
+        assert_eq!(tree.reparent(a, Some(c)), Err(EdgeError::Cycle { from: c, to: a }));
+        assert_eq!(tree.reparent(b, Some(b)), Err(EdgeError::SelfLoop(b)));
+        assert_eq!(tree.edges, before);
+    }
+
+    #[test]
+    fn connect_and_disconnect() {
+        let (mut tree, [a, b, c, d]) = sample();
+        tree.add_edge(d, c, EdgeKind::Requires).unwrap();
+        assert_eq!(parents(&tree, c), vec![b, d]);
+        assert_eq!(tree.add_edge(d, c, EdgeKind::Requires), Err(EdgeError::Duplicate { from: d, to: c }));
+        assert_eq!(tree.add_edge(c, a, EdgeKind::Recommends), Err(EdgeError::Cycle { from: c, to: a }));
+        assert_eq!(tree.remove_edge(d, c).map(|e| e.kind), Some(EdgeKind::Requires));
+        assert_eq!(tree.remove_edge(d, c), None);
+        assert_eq!(parents(&tree, c), vec![b]);
+    }
+
+    #[test]
+    fn move_task_reorders() {
+        let (mut tree, [a, ..]) = sample();
+        tree.node_mut(a).unwrap().tasks = vec![Task::new("1"), Task::new("2"), Task::new("3")];
+        tree.move_task(a, 0, 2).unwrap();
+        let order: Vec<&str> = tree.node(a).unwrap().tasks.iter().map(|t| t.content.as_str()).collect();
+        assert_eq!(order, vec!["2", "3", "1"]);
+        assert_eq!(tree.move_task(a, 0, 3), Err(TaskError::UnknownTask { node: a, index: 3 }));
+    }
+}
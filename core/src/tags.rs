@@ -0,0 +1,199 @@
+//! Tags, categories and colours on nodes, and filtering a tree by them.
+
+use crate::skill_tree::{GNode, GTree, NodeId};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// An sRGB colour, saved as `"#rrggbb"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        // `from_str_radix` alone would also take a sign, as in "+f".
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("'{}' is not a #rrggbb colour", s));
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).expect("two hex digits");
+        Ok(Rgb(channel(0), channel(2), channel(4)))
+    }
+}
+
+impl Serialize for Rgb {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rgb {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Tags compare case-insensitively and ignore surrounding whitespace.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+impl GNode {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        self.tags.iter().any(|t| normalize_tag(t) == tag)
+    }
+
+    /// Adds a tag unless the node already has it. Returns whether it was added.
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        if tag.trim().is_empty() || self.has_tag(tag) {
+            return false;
+        }
+        self.tags.push(tag.trim().to_string());
+        true
+    }
+
+    /// Returns whether the node had the tag.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        let before = self.tags.len();
+        self.tags.retain(|t| normalize_tag(t) != tag);
+        self.tags.len() != before
+    }
+}
+
+/// Which nodes to show. An empty filter matches every node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagFilter {
+    /// Normalized tags, see `normalize_tag`.
+    pub tags: BTreeSet<String>,
+    /// Require every tag instead of any of them.
+    pub match_all: bool,
+    pub category: Option<String>,
+}
+
+impl TagFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.category.is_none()
+    }
+
+    /// Adds the tag if it isn't selected yet and removes it otherwise.
+    pub fn toggle(&mut self, tag: &str) {
+        let tag = normalize_tag(tag);
+        if !self.tags.remove(&tag) {
+            self.tags.insert(tag);
+        }
+    }
+
+    pub fn matches(&self, node: &GNode) -> bool {
+        if let Some(category) = &self.category
+            && node.category.as_deref().map(normalize_tag) != Some(normalize_tag(category))
+        {
+            return false;
+        }
+        if self.tags.is_empty() {
+            return true;
+        }
+        if self.match_all {
+            self.tags.iter().all(|t| node.has_tag(t))
+        } else {
+            self.tags.iter().any(|t| node.has_tag(t))
+        }
+    }
+}
+
+impl GTree {
+    /// Ids of the nodes matching `filter`, in tree order.
+    pub fn filter(&self, filter: &TagFilter) -> Vec<NodeId> {
+        self.nodes.iter().filter(|n| filter.matches(n)).map(|n| n.id).collect()
+    }
+
+    /// Every tag used in the tree, normalized and sorted.
+    pub fn all_tags(&self) -> BTreeSet<String> {
+        self.nodes.iter().flat_map(|n| n.tags.iter().map(|t| normalize_tag(t))).filter(|t| !t.is_empty()).collect()
+    }
+
+    /// Every category used in the tree, sorted.
+    pub fn categories(&self) -> BTreeSet<String> {
+        self.nodes.iter().filter_map(|n| n.category.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(tags: &[&str], category: Option<&str>) -> GNode {
+        let mut node = GNode::new("Node");
+        node.tags = tags.iter().map(|t| t.to_string()).collect();
+        node.category = category.map(String::from);
+        node
+    }
+
+    #[test]
+    fn colours_need_six_hex_digits() {
+        assert_eq!("#1a2B3c".parse(), Ok(Rgb(0x1a, 0x2b, 0x3c)));
+        assert_eq!("ffffff".parse(), Ok(Rgb(255, 255, 255)));
+        assert_eq!(Rgb(1, 2, 255).to_string(), "#0102ff");
+        for bad in ["#+f+f+f", "#-1-1-1", "#fff", "#1234567", "#12345g", "#ééé", ""] {
+            assert!(bad.parse::<Rgb>().is_err(), "{} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn tags_compare_loosely() {
+        let mut n = node(&["Rust"], None);
+        assert!(n.has_tag(" rust "));
+        assert!(!n.add_tag("RUST"));
+        assert!(!n.add_tag("  "));
+        assert!(n.add_tag(" Async "));
+        assert_eq!(n.tags, ["Rust", "Async"]);
+        assert!(n.remove_tag("async"));
+        assert!(!n.remove_tag("async"));
+    }
+
+    #[test]
+    fn filter_matches_any_or_all_tags_within_a_category() {
+        let mut filter = TagFilter::new();
+        let both = node(&["rust", "web"], Some("Work"));
+        let one = node(&["Rust"], None);
+        assert!(filter.is_empty());
+        assert!(filter.matches(&both) && filter.matches(&one));
+
+        filter.toggle("RUST");
+        filter.toggle("web");
+        assert!(filter.matches(&both) && filter.matches(&one));
+        filter.match_all = true;
+        assert!(filter.matches(&both) && !filter.matches(&one));
+        filter.toggle("Web");
+        assert!(filter.matches(&one));
+
+        filter.category = Some(" work".to_string());
+        assert!(filter.matches(&both) && !filter.matches(&one));
+    }
+
+    #[test]
+    fn tree_lists_matches_tags_and_categories() {
+        let mut tree = GTree::new("Tree");
+        let a = tree.add_node(node(&["Rust", " "], Some("Work")));
+        tree.add_node(node(&["go"], Some("Play")));
+        let mut filter = TagFilter::new();
+        filter.toggle("rust");
+        assert_eq!(tree.filter(&filter), [a]);
+        assert_eq!(tree.all_tags(), ["go", "rust"].map(String::from).into());
+        assert_eq!(tree.categories(), ["Play", "Work"].map(String::from).into());
+    }
+}
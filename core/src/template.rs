@@ -0,0 +1,345 @@
+//! Reusable tree outlines with `{{placeholders}}` and optional nodes.
+//!
+//! A template holds the tree as plain JSON so a placeholder can stand in any text, and
+//! also for numbers: in a numeric field such as a measure's `target`, a string that is
+//! exactly `"{{wpm}}"` becomes the number it is filled in with.
+//! `instantiate` fills in the parameters and returns a tree with all progress reset.
+
+use crate::error::Error;
+use crate::migrate;
+use crate::skill_tree::{GTree, NodeId, RemovePolicy};
+use crate::validate::Severity;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Param {
+    /// What goes between the braces, e.g. `language` for `{{language}}`.
+    pub name: String,
+    /// The question to ask the user; the name is used when empty.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+impl Param {
+    pub fn prompt(&self) -> &str {
+        if self.prompt.is_empty() { &self.name } else { &self.prompt }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub params: Vec<Param>,
+    /// Nodes the user may leave out. Their dependents move up to the node's prerequisites.
+    #[serde(default)]
+    pub optional: Vec<NodeId>,
+    /// A tree document, any version `load_tree_from_file` reads.
+    pub tree: Value,
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    /// A parameter without a value or default.
+    MissingParam(String),
+    /// A `{{placeholder}}` that isn't one of the template's parameters.
+    UnknownPlaceholder(String),
+    /// A node asked to be left out that isn't listed as optional.
+    NotOptional(NodeId),
+    /// The filled in tree doesn't load.
+    Tree(Error),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::MissingParam(name) => write!(f, "no value for parameter '{}'", name),
+            TemplateError::UnknownPlaceholder(name) => write!(f, "{{{{{}}}}} is not a parameter of the template", name),
+            TemplateError::NotOptional(id) => write!(f, "node {} is not optional", id),
+            TemplateError::Tree(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TemplateError::Tree(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for TemplateError {
+    fn from(e: Error) -> Self {
+        TemplateError::Tree(e)
+    }
+}
+
+impl Template {
+    /// Every placeholder name used in the tree.
+    pub fn placeholders(&self) -> BTreeSet<String> {
+        let mut out = BTreeSet::new();
+        visit_strings(&self.tree, &mut |s| {
+            for (name, _) in placeholders_in(s) {
+                out.insert(name.to_string());
+            }
+        });
+        out
+    }
+
+    /// The title of a node in the template, placeholders and all.
+    pub fn node_title(&self, id: NodeId) -> Option<&str> {
+        let nodes = self.tree.get("nodes")?.as_array()?;
+        let node = nodes.iter().find(|n| n.get("id").and_then(Value::as_u64) == Some(id.0 as u64))?;
+        node.get("title")?.as_str()
+    }
+
+    /// Fills in `values`, falling back to each parameter's default, leaves out the nodes in `skip`
+    /// and returns a fresh tree: no progress, empty habit logs and no activity.
+    pub fn instantiate(&self, values: &HashMap<String, String>, skip: &HashSet<NodeId>) -> Result<GTree, TemplateError> {
+        let mut resolved = HashMap::new();
+        for param in &self.params {
+            let value = values.get(&param.name).or(param.default.as_ref());
+            let value = value.ok_or_else(|| TemplateError::MissingParam(param.name.clone()))?;
+            resolved.insert(param.name.as_str(), value.as_str());
+        }
+        if let Some(id) = skip.iter().find(|id| !self.optional.contains(id)) {
+            return Err(TemplateError::NotOptional(*id));
+        }
+
+        let mut doc = self.tree.clone();
+        substitute(&mut doc, false, &resolved)?;
+        migrate::migrate(&mut doc)?;
+        let mut tree: GTree = serde_json::from_value(doc).map_err(Error::from)?;
+        for &id in skip {
+            tree.remove_node_with(id, RemovePolicy::Rehome);
+        }
+        tree.reset_progress();
+
+        let errors: Vec<_> = tree.validate().into_iter().filter(|d| d.severity == Severity::Error).collect();
+        if !errors.is_empty() {
+            return Err(Error::Invalid(errors).into());
+        }
+        Ok(tree)
+    }
+}
+
+/// `(name, byte range)` of every `{{name}}` in `s`.
+fn placeholders_in(s: &str) -> Vec<(&str, std::ops::Range<usize>)> {
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(start) = s[from..].find("{{").map(|i| from + i) {
+        let Some(end) = s[start + 2..].find("}}").map(|i| start + 2 + i) else { break };
+        out.push((s[start + 2..end].trim(), start..end + 2));
+        from = end + 2;
+    }
+    out
+}
+
+fn visit_strings(value: &Value, f: &mut impl FnMut(&str)) {
+    match value {
+        Value::String(s) => f(s),
+        Value::Array(items) => items.iter().for_each(|v| visit_strings(v, f)),
+        Value::Object(map) => map.values().for_each(|v| visit_strings(v, f)),
+        _ => {}
+    }
+}
+
+/// Fields of the tree schema that hold numbers.
+const NUMERIC_FIELDS: &[&str] = &["x", "y", "r", "weight", "start", "target", "current", "target_streak", "times_per_week", "estimate"];
+
+/// Whole numbers stay integers so they also fill fields like `target_streak` that take no fractions.
+fn parse_number(s: &str) -> Option<serde_json::Number> {
+    if let Ok(n) = s.parse::<u64>() {
+        return Some(n.into());
+    }
+    if let Ok(n) = s.parse::<i64>() {
+        return Some(n.into());
+    }
+    s.parse::<f64>().ok().and_then(serde_json::Number::from_f64)
+}
+
+/// Replaces placeholders in every string below `value`. `numeric` is set when `value`
+/// sits in one of the `NUMERIC_FIELDS`.
+fn substitute(value: &mut Value, numeric: bool, values: &HashMap<&str, &str>) -> Result<(), TemplateError> {
+    match value {
+        Value::String(s) => {
+            let found = placeholders_in(s);
+            if found.is_empty() {
+                return Ok(());
+            }
+            let mut out = String::new();
+            let mut last = 0;
+            for (name, range) in &found {
+                let v = values.get(name).ok_or_else(|| TemplateError::UnknownPlaceholder(name.to_string()))?;
+                out.push_str(&s[last..range.start]);
+                out.push_str(v);
+                last = range.end;
+            }
+            out.push_str(&s[last..]);
+            let whole = found.len() == 1 && found[0].1 == (0..s.len());
+            *value = match parse_number(out.trim()) {
+                Some(n) if numeric && whole => Value::Number(n),
+                _ => Value::String(out),
+            };
+        }
+        Value::Array(items) => {
+            for v in items {
+                substitute(v, false, values)?;
+            }
+        }
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                substitute(v, NUMERIC_FIELDS.contains(&key.as_str()), values)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub fn load_template_from_file(path: &str) -> Result<Template, Error> {
+    let json = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::habit::Schedule;
+    use crate::skill_tree::{EdgeKind, TaskKind};
+    use serde_json::json;
+
+    /// a -> b -> c with b optional; b's title and c's tasks use the parameters.
+    fn sample() -> Template {
+        serde_json::from_value(json!({
+            "name": "Sample",
+            "params": [
+                { "name": "subject" },
+                { "name": "target", "default": "72.5" },
+                { "name": "days", "default": "14" },
+                { "name": "times", "default": "3" },
+            ],
+            "optional": [1],
+            "tree": {
+                "version": 1,
+                "title": "Learn {{subject}}",
+                "nodes": [
+                    { "id": 0, "title": "Basics", "tasks": [] },
+                    { "id": 1, "title": "{{subject}} drills", "tasks": [] },
+                    {
+                        "id": 2,
+                        "title": "{{days}}",
+                        "tasks": [
+                            {
+                                "content": "Reach {{target}}",
+                                "checked": true,
+                                "kind": { "type": "measure", "unit": "kg", "target": "{{target}}", "current": 3.0 }
+                            },
+                            {
+                                "content": "Practise",
+                                "checked": false,
+                                "kind": {
+                                    "type": "habit",
+                                    "schedule": { "times_per_week": "{{times}}" },
+                                    "target_streak": "{{days}}"
+                                }
+                            }
+                        ]
+                    }
+                ],
+                "edges": [
+                    { "from": 0, "to": 1, "kind": "requires" },
+                    { "from": 1, "to": 2, "kind": "requires" }
+                ]
+            }
+        }))
+        .unwrap()
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn placeholders_fill_text_and_numbers() {
+        let template = sample();
+        assert_eq!(template.placeholders(), ["days", "subject", "target", "times"].map(String::from).into());
+        let tree = template.instantiate(&values(&[("subject", "Rust")]), &HashSet::new()).unwrap();
+
+        assert_eq!(tree.title, "Learn Rust");
+        assert_eq!(tree.nodes[1].title, "Rust drills");
+        // Outside numeric fields a number stays text.
+        assert_eq!(tree.nodes[2].title, "14");
+        let tasks = &tree.nodes[2].tasks;
+        assert_eq!(tasks[0].content, "Reach 72.5");
+        let TaskKind::Measure(m) = &tasks[0].kind else { panic!("not a measure") };
+        assert_eq!(m.target, 72.5);
+        // Progress is reset.
+        assert_eq!(m.current, m.start);
+        assert!(!tasks[0].checked);
+        let TaskKind::Habit(h) = &tasks[1].kind else { panic!("not a habit") };
+        assert_eq!(h.target_streak, 14);
+        assert_eq!(h.schedule, Schedule::TimesPerWeek(3));
+    }
+
+    #[test]
+    fn skipped_optional_nodes_pass_their_prerequisites_on() {
+        let skip = HashSet::from([NodeId(1)]);
+        let tree = sample().instantiate(&values(&[("subject", "Rust")]), &skip).unwrap();
+        let ids: Vec<_> = tree.nodes.iter().map(|n| n.id).collect();
+        assert_eq!(ids, [NodeId(0), NodeId(2)]);
+        let edges: Vec<_> = tree.edges.iter().map(|e| (e.from, e.to, e.kind)).collect();
+        assert_eq!(edges, [(NodeId(0), NodeId(2), EdgeKind::Requires)]);
+    }
+
+    #[test]
+    fn missing_and_unknown_parameters_are_refused() {
+        let template = sample();
+        match template.instantiate(&HashMap::new(), &HashSet::new()) {
+            Err(TemplateError::MissingParam(name)) => assert_eq!(name, "subject"),
+            other => panic!("expected MissingParam, got {:?}", other),
+        }
+
+        let mut template = sample();
+        template.tree["nodes"][0]["description"] = json!("See {{ nowhere }}");
+        match template.instantiate(&values(&[("subject", "Rust")]), &HashSet::new()) {
+            Err(TemplateError::UnknownPlaceholder(name)) => assert_eq!(name, "nowhere"),
+            other => panic!("expected UnknownPlaceholder, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn only_optional_nodes_can_be_skipped() {
+        let skip = HashSet::from([NodeId(2)]);
+        match sample().instantiate(&values(&[("subject", "Rust")]), &skip) {
+            Err(TemplateError::NotOptional(id)) => assert_eq!(id, NodeId(2)),
+            other => panic!("expected NotOptional, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bundled_typing_speed_template_instantiates() {
+        let path = format!("{}/../templates/typing_speed.json", env!("CARGO_MANIFEST_DIR"));
+        let template = load_template_from_file(&path).unwrap();
+        let tree = template.instantiate(&HashMap::new(), &HashSet::new()).unwrap();
+        assert_eq!(tree.title, "Type 60 WPM");
+        assert_eq!(tree.nodes.len(), 3);
+
+        let skip = template.optional.iter().copied().collect();
+        let tree = template.instantiate(&values(&[("wpm", "90")]), &skip).unwrap();
+        assert_eq!(tree.title, "Type 90 WPM");
+        assert_eq!(tree.nodes.len(), 2);
+        let TaskKind::Measure(m) = &tree.nodes[1].tasks[0].kind else { panic!("not a measure") };
+        assert_eq!(m.target, 90.0);
+    }
+}
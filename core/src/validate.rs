@@ -0,0 +1,414 @@
+//! Consistency checks for loaded or edited trees.
+//!
+//! `GTree::validate` never fails; it returns every problem it finds together
+//! with the path of the offending value, e.g. `nodes[3].r` or `edges[1].from`.
+
+use crate::habit::{Habit, Schedule};
+use crate::resource::Target;
+use crate::workspace::{NodeRef, TreeId};
+use crate::skill_tree::{Direction, GTree, Measure, NodeId, TaskKind};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Suspicious but harmless, the tree works as is.
+    Warning,
+    /// The tree is broken and parts of it won't behave.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    EmptyTitle,
+    EmptyText,
+    DuplicateId(NodeId),
+    /// A value outside the range the field allows.
+    OutOfRange { value: f32, expected: &'static str },
+    /// An edge pointing at a node that does not exist.
+    DanglingReference(NodeId),
+    SelfLoop(NodeId),
+    DuplicateEdge { from: NodeId, to: NodeId },
+    /// A list that has to be sorted without duplicates isn't.
+    Unsorted,
+    /// The nodes along a prerequisite cycle, in edge order.
+    Cycle(Vec<NodeId>),
+    /// A workspace link to a tree the workspace doesn't have.
+    UnknownTree(TreeId),
+    /// A workspace link between two nodes of the same tree.
+    SameTreeLink,
+    /// More than one workspace tree with this id.
+    DuplicateTree(TreeId),
+    /// A workspace link whose prerequisite already depends on its dependent, through the
+    /// edges and links of any of the trees.
+    LinkCycle { from: NodeRef, to: NodeRef },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::EmptyTitle => write!(f, "title is empty"),
+            Problem::EmptyText => write!(f, "text is empty"),
+            Problem::DuplicateId(id) => write!(f, "id {} is used by more than one node", id),
+            Problem::OutOfRange { value, expected } => write!(f, "{} is out of range, expected {}", value, expected),
+            Problem::Unsorted => write!(f, "entries are not sorted or contain duplicates"),
+            Problem::DanglingReference(id) => write!(f, "refers to missing node {}", id),
+            Problem::SelfLoop(id) => write!(f, "node {} is its own prerequisite", id),
+            Problem::DuplicateEdge { from, to } => write!(f, "edge {} -> {} appears more than once", from, to),
+            Problem::Cycle(nodes) => {
+                let ids: Vec<String> = nodes.iter().map(|id| id.to_string()).collect();
+                write!(f, "prerequisite cycle {} -> {}", ids.join(" -> "), ids[0])
+            }
+            Problem::UnknownTree(id) => write!(f, "refers to missing {}", id),
+            Problem::SameTreeLink => write!(f, "links two nodes of the same tree"),
+            Problem::DuplicateTree(id) => write!(f, "{} appears more than once", id),
+            Problem::LinkCycle { from, to } => write!(f, "link {} -> {} closes a prerequisite cycle", from, to),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Where in the document the problem is, e.g. `nodes[2].tasks[0].content`.
+    pub path: String,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", level, self.path, self.problem)
+    }
+}
+
+impl Diagnostic {
+    pub(crate) fn new(severity: Severity, path: String, problem: Problem) -> Self {
+        Diagnostic { severity, path, problem }
+    }
+}
+
+/// True if any of `diagnostics` is an error.
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+impl GTree {
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut out = Vec::new();
+        if self.title.trim().is_empty() {
+            out.push(Diagnostic::new(Severity::Warning, "title".into(), Problem::EmptyTitle));
+        }
+        self.validate_nodes(&mut out);
+        self.validate_edges(&mut out);
+        self.validate_cycles(&mut out);
+        out
+    }
+
+    fn validate_nodes(&self, out: &mut Vec<Diagnostic>) {
+        let mut seen = HashSet::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let path = format!("nodes[{}]", i);
+            if !seen.insert(node.id) {
+                out.push(Diagnostic::new(Severity::Error, format!("{}.id", path), Problem::DuplicateId(node.id)));
+            }
+            if node.title.trim().is_empty() {
+                out.push(Diagnostic::new(Severity::Warning, format!("{}.title", path), Problem::EmptyTitle));
+            }
+            for (field, value) in [("x", node.x), ("y", node.y)] {
+                if !value.is_finite() {
+                    out.push(Diagnostic::new(
+                        Severity::Error,
+                        format!("{}.{}", path, field),
+                        Problem::OutOfRange { value, expected: "a finite number" },
+                    ));
+                }
+            }
+            check_weight(node.weight, &path, out);
+            check_estimate(node.estimate, &path, out);
+            if !(node.r.is_finite() && node.r > 0.0) {
+                out.push(Diagnostic::new(
+                    Severity::Error,
+                    format!("{}.r", path),
+                    Problem::OutOfRange { value: node.r, expected: "a positive radius" },
+                ));
+            }
+            for (j, task) in node.tasks.iter().enumerate() {
+                let task_path = format!("{}.tasks[{}]", path, j);
+                check_weight(task.weight, &task_path, out);
+                check_estimate(task.estimate, &task_path, out);
+                if task.content.trim().is_empty() {
+                    out.push(Diagnostic::new(
+                        Severity::Warning,
+                        format!("{}.content", task_path),
+                        Problem::EmptyText,
+                    ));
+                }
+                match &task.kind {
+                    TaskKind::Check => {}
+                    TaskKind::Measure(m) => validate_measure(m, &task_path, out),
+                    TaskKind::Habit(h) => validate_habit(h, &task_path, out),
+                }
+            }
+            for (j, tag) in node.tags.iter().enumerate() {
+                if tag.trim().is_empty() {
+                    out.push(Diagnostic::new(Severity::Warning, format!("{}.tags[{}]", path, j), Problem::EmptyText));
+                }
+            }
+            for (j, resource) in node.resources.iter().enumerate() {
+                let empty = match &resource.target {
+                    Target::Url(url) => url.trim().is_empty(),
+                    Target::Path(path) => path.as_os_str().is_empty(),
+                };
+                if empty {
+                    out.push(Diagnostic::new(
+                        Severity::Warning,
+                        format!("{}.resources[{}]", path, j),
+                        Problem::EmptyText,
+                    ));
+                }
+            }
+            for (j, goal) in node.goals.iter().enumerate() {
+                if goal.text.trim().is_empty() {
+                    out.push(Diagnostic::new(
+                        Severity::Warning,
+                        format!("{}.goals[{}].text", path, j),
+                        Problem::EmptyText,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn validate_edges(&self, out: &mut Vec<Diagnostic>) {
+        let mut seen = HashSet::new();
+        for (i, edge) in self.edges.iter().enumerate() {
+            let path = format!("edges[{}]", i);
+            for (field, id) in [("from", edge.from), ("to", edge.to)] {
+                if self.node(id).is_none() {
+                    out.push(Diagnostic::new(
+                        Severity::Error,
+                        format!("{}.{}", path, field),
+                        Problem::DanglingReference(id),
+                    ));
+                }
+            }
+            if edge.from == edge.to {
+                out.push(Diagnostic::new(Severity::Error, path.clone(), Problem::SelfLoop(edge.from)));
+            }
+            if !seen.insert((edge.from, edge.to)) {
+                out.push(Diagnostic::new(
+                    Severity::Warning,
+                    path,
+                    Problem::DuplicateEdge { from: edge.from, to: edge.to },
+                ));
+            }
+        }
+    }
+
+    /// Depth-first search over all edges; every edge closing a loop is reported once.
+    fn validate_cycles(&self, out: &mut Vec<Diagnostic>) {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            Active,
+            Done,
+        }
+        let mut marks: HashMap<NodeId, Mark> = HashMap::new();
+        for node in &self.nodes {
+            if marks.contains_key(&node.id) {
+                continue;
+            }
+            // Stack of (node, index of the next outgoing edge to look at); doubles as the current path.
+            let mut stack: Vec<(NodeId, usize)> = vec![(node.id, 0)];
+            marks.insert(node.id, Mark::Active);
+            while let Some(top) = stack.last_mut() {
+                let (current, next) = *top;
+                top.1 += 1;
+                let edge = self.edges.iter().enumerate().filter(|(_, e)| e.from == current).nth(next);
+                let Some((i, edge)) = edge else {
+                    marks.insert(current, Mark::Done);
+                    stack.pop();
+                    continue;
+                };
+                if edge.from == edge.to || self.node(edge.to).is_none() {
+                    continue; // reported by validate_edges
+                }
+                match marks.get(&edge.to) {
+                    Some(Mark::Active) => {
+                        let start = stack.iter().position(|(id, _)| *id == edge.to).unwrap_or(0);
+                        let cycle = stack[start..].iter().map(|(id, _)| *id).collect();
+                        out.push(Diagnostic::new(Severity::Error, format!("edges[{}]", i), Problem::Cycle(cycle)));
+                    }
+                    Some(Mark::Done) => {}
+                    None => {
+                        marks.insert(edge.to, Mark::Active);
+                        stack.push((edge.to, 0));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn check_weight(weight: f32, path: &str, out: &mut Vec<Diagnostic>) {
+    if !(weight.is_finite() && weight >= 0.0) {
+        out.push(Diagnostic::new(
+            Severity::Error,
+            format!("{}.weight", path),
+            Problem::OutOfRange { value: weight, expected: "a weight of 0 or more" },
+        ));
+    }
+}
+
+fn check_estimate(estimate: Option<f32>, path: &str, out: &mut Vec<Diagnostic>) {
+    if let Some(hours) = estimate
+        && !(hours.is_finite() && hours >= 0.0)
+    {
+        out.push(Diagnostic::new(
+            Severity::Error,
+            format!("{}.estimate", path),
+            Problem::OutOfRange { value: hours, expected: "an estimate of 0 hours or more" },
+        ));
+    }
+}
+
+fn validate_measure(m: &Measure, path: &str, out: &mut Vec<Diagnostic>) {
+    for (field, value) in [("start", m.start), ("target", m.target), ("current", m.current)] {
+        if !value.is_finite() {
+            out.push(Diagnostic::new(
+                Severity::Error,
+                format!("{}.{}", path, field),
+                Problem::OutOfRange { value, expected: "a finite number" },
+            ));
+        }
+    }
+    let (ok, expected) = match m.direction {
+        Direction::Increase => (m.target > m.start, "a target above the start"),
+        Direction::Decrease => (m.target < m.start, "a target below the start"),
+    };
+    if !ok {
+        out.push(Diagnostic::new(
+            Severity::Warning,
+            format!("{}.target", path),
+            Problem::OutOfRange { value: m.target, expected },
+        ));
+    }
+}
+
+fn validate_habit(h: &Habit, path: &str, out: &mut Vec<Diagnostic>) {
+    if let Schedule::TimesPerWeek(times) = h.schedule
+        && !(1..=7).contains(&times)
+    {
+        out.push(Diagnostic::new(
+            Severity::Error,
+            format!("{}.schedule", path),
+            Problem::OutOfRange { value: times as f32, expected: "1 to 7 times per week" },
+        ));
+    }
+    if !h.log.is_sorted() || h.log.windows(2).any(|w| w[0] == w[1]) {
+        out.push(Diagnostic::new(
+            Severity::Error,
+            format!("{}.log", path),
+            Problem::Unsorted,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill_tree::{Edge, EdgeKind, GNode};
+
+    /// A tree with `n` nodes, ids 0..n, and the given `Requires` edges, none of them checked.
+    fn tree_with(n: u32, edges: &[(u32, u32)]) -> GTree {
+        let mut tree = GTree::new("Tree");
+        for i in 0..n {
+            let mut node = GNode::new(format!("n{}", i));
+            node.id = NodeId(i);
+            tree.nodes.push(node);
+        }
+        for &(from, to) in edges {
+            tree.edges.push(Edge { from: NodeId(from), to: NodeId(to), kind: EdgeKind::Requires });
+        }
+        tree
+    }
+
+    fn error(path: &str, problem: Problem) -> Diagnostic {
+        Diagnostic::new(Severity::Error, path.to_string(), problem)
+    }
+
+    #[test]
+    fn sound_tree_has_no_problems() {
+        assert!(tree_with(3, &[(0, 1), (1, 2), (0, 2)]).validate().is_empty());
+    }
+
+    #[test]
+    fn cycles_are_reported_once_in_edge_order() {
+        let tree = tree_with(4, &[(0, 1), (1, 2), (2, 3), (3, 1)]);
+        let cycle = Problem::Cycle(vec![NodeId(1), NodeId(2), NodeId(3)]);
+        assert_eq!(tree.validate(), [error("edges[3]", cycle.clone())]);
+        assert_eq!(cycle.to_string(), "prerequisite cycle #1 -> #2 -> #3 -> #1");
+
+        // The path starts where the search entered the loop; the edge that closes it is reported.
+        let tree = tree_with(2, &[(1, 0), (0, 1)]);
+        assert_eq!(tree.validate(), [error("edges[0]", Problem::Cycle(vec![NodeId(0), NodeId(1)]))]);
+    }
+
+    #[test]
+    fn duplicate_ids_are_errors() {
+        let mut tree = tree_with(2, &[]);
+        tree.nodes[1].id = NodeId(0);
+        assert_eq!(tree.validate(), [error("nodes[1].id", Problem::DuplicateId(NodeId(0)))]);
+    }
+
+    #[test]
+    fn dangling_edges_are_errors() {
+        let tree = tree_with(1, &[(0, 9), (7, 0)]);
+        assert_eq!(
+            tree.validate(),
+            [
+                error("edges[0].to", Problem::DanglingReference(NodeId(9))),
+                error("edges[1].from", Problem::DanglingReference(NodeId(7)))
+            ]
+        );
+    }
+
+    #[test]
+    fn self_loops_are_errors_but_not_cycles() {
+        let tree = tree_with(1, &[(0, 0)]);
+        assert_eq!(tree.validate(), [error("edges[0]", Problem::SelfLoop(NodeId(0)))]);
+    }
+
+    #[test]
+    fn duplicate_edges_are_warnings() {
+        let mut tree = tree_with(2, &[(0, 1)]);
+        tree.edges.push(Edge { from: NodeId(0), to: NodeId(1), kind: EdgeKind::Recommends });
+        let duplicate = Problem::DuplicateEdge { from: NodeId(0), to: NodeId(1) };
+        assert_eq!(tree.validate(), [Diagnostic::new(Severity::Warning, "edges[1]".to_string(), duplicate)]);
+        assert!(!has_errors(&tree.validate()));
+    }
+
+    #[test]
+    fn positions_and_radius_must_be_finite() {
+        let mut tree = tree_with(2, &[]);
+        tree.nodes[0].x = f32::NAN;
+        tree.nodes[0].y = f32::INFINITY;
+        tree.nodes[1].r = 0.0;
+        tree.nodes[1].y = f32::NEG_INFINITY;
+        let found: Vec<(Severity, String)> = tree.validate().into_iter().map(|d| (d.severity, d.path)).collect();
+        let paths = ["nodes[0].x", "nodes[0].y", "nodes[1].y", "nodes[1].r"];
+        let expected = paths.map(|p| (Severity::Error, p.to_string()));
+        assert_eq!(found, expected);
+
+        tree.nodes[0].x = 0.0;
+        tree.nodes[0].y = 0.0;
+        tree.nodes[1].y = 0.0;
+        tree.nodes[1].r = f32::NAN;
+        let diagnostics = tree.validate();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(diagnostics[0].problem, Problem::OutOfRange { value, .. } if value.is_nan()));
+    }
+}
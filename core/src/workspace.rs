@@ -0,0 +1,629 @@
+//! Several trees kept together, with prerequisites that cross from one tree into another.
+//!
+//! A workspace file only lists its trees and the links between them; every tree stays
+//! in its own file, stored relative to the workspace file, and still opens on its own.
+
+use crate::command::{Command, CommandError, History};
+use crate::error::Error;
+use crate::resource::relative_to;
+use crate::skill_tree::{
+    weighted_progress, EdgeKind, GNode, GTree, Mode, NodeId, NodeState, TaskError, load_tree_from_file, save_tree_to_file,
+};
+use crate::validate::{Diagnostic, Problem, Severity};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The workspace file schema `save_workspace_to_file` writes.
+pub const WORKSPACE_VERSION: u64 = 1;
+
+/// Identifies a tree within its workspace. Never reused for another tree.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct TreeId(pub u32);
+
+impl fmt::Display for TreeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tree {}", self.0)
+    }
+}
+
+/// A node in one of the workspace's trees.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeRef {
+    pub tree: TreeId,
+    pub node: NodeId,
+}
+
+impl fmt::Display for NodeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {}", self.node, self.tree)
+    }
+}
+
+/// A prerequisite edge between nodes of two different trees.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    pub from: NodeRef,
+    pub to: NodeRef,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub id: TreeId,
+    /// The tree's file, relative to the workspace file. `None` until the workspace is saved.
+    pub path: Option<PathBuf>,
+    pub tree: GTree,
+}
+
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub name: String,
+    pub members: Vec<Member>,
+    pub links: Vec<Link>,
+    /// The id `add_tree` hands out next; saved so ids of removed trees stay retired.
+    next_id: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkError {
+    UnknownNode(NodeRef),
+    /// Both ends are in the same tree; that is a regular edge of the tree.
+    SameTree(TreeId),
+    Duplicate { from: NodeRef, to: NodeRef },
+    /// `from` already depends on `to`, across however many trees.
+    Cycle { from: NodeRef, to: NodeRef },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::UnknownNode(r) => write!(f, "{} does not exist", r),
+            LinkError::SameTree(tree) => write!(f, "both nodes are in {}, use an edge of the tree instead", tree),
+            LinkError::Duplicate { from, to } => write!(f, "{} is already linked to {}", from, to),
+            LinkError::Cycle { from, to } => write!(f, "linking {} to {} would create a cycle", from, to),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// One line of the dashboard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeSummary {
+    pub id: TreeId,
+    pub title: String,
+    pub progress: f32,
+    pub nodes: usize,
+    pub complete: usize,
+    /// Links into this tree whose prerequisite in another tree isn't complete yet.
+    pub waiting_on: Vec<Link>,
+}
+
+impl Workspace {
+    pub fn new(name: impl Into<String>) -> Self {
+        Workspace { name: name.into(), members: Vec::new(), links: Vec::new(), next_id: 0 }
+    }
+
+    /// Adds a tree under a fresh id. `path` is relative to the workspace file.
+    pub fn add_tree(&mut self, tree: GTree, path: Option<PathBuf>) -> TreeId {
+        let id = TreeId(self.next_id);
+        self.next_id += 1;
+        self.members.push(Member { id, path, tree });
+        id
+    }
+
+    /// Removes a tree together with every link touching it.
+    pub fn remove_tree(&mut self, id: TreeId) -> Option<Member> {
+        let i = self.members.iter().position(|m| m.id == id)?;
+        self.links.retain(|l| l.from.tree != id && l.to.tree != id);
+        Some(self.members.remove(i))
+    }
+
+    pub fn member(&self, id: TreeId) -> Option<&Member> {
+        self.members.iter().find(|m| m.id == id)
+    }
+
+    pub fn tree(&self, id: TreeId) -> Option<&GTree> {
+        self.member(id).map(|m| &m.tree)
+    }
+
+    pub fn tree_mut(&mut self, id: TreeId) -> Option<&mut GTree> {
+        self.members.iter_mut().find(|m| m.id == id).map(|m| &mut m.tree)
+    }
+
+    pub fn node(&self, r: NodeRef) -> Option<&GNode> {
+        self.tree(r.tree)?.node(r.node)
+    }
+
+    /// Adds a prerequisite from a node of one tree to a node of another.
+    pub fn add_link(&mut self, from: NodeRef, to: NodeRef, kind: EdgeKind) -> Result<(), LinkError> {
+        for r in [from, to] {
+            if self.node(r).is_none() {
+                return Err(LinkError::UnknownNode(r));
+            }
+        }
+        if from.tree == to.tree {
+            return Err(LinkError::SameTree(from.tree));
+        }
+        if self.link(from, to).is_some() {
+            return Err(LinkError::Duplicate { from, to });
+        }
+        if self.depends_on(from, to) {
+            return Err(LinkError::Cycle { from, to });
+        }
+        self.links.push(Link { from, to, kind });
+        Ok(())
+    }
+
+    pub fn remove_link(&mut self, from: NodeRef, to: NodeRef) -> Option<Link> {
+        let i = self.links.iter().position(|l| l.from == from && l.to == to)?;
+        Some(self.links.remove(i))
+    }
+
+    pub fn link(&self, from: NodeRef, to: NodeRef) -> Option<&Link> {
+        self.links.iter().find(|l| l.from == from && l.to == to)
+    }
+
+    /// The first link whose prerequisite depends on its dependent again. Links are checked when
+    /// they are added, but an edge added to a tree later, or while it was open on its own,
+    /// can still close a cycle.
+    pub fn link_cycle(&self) -> Option<Link> {
+        self.links.iter().find(|l| self.depends_on(l.from, l.to)).copied()
+    }
+
+    /// Everything `r` needs, from its own tree and from linked trees.
+    pub fn prerequisites(&self, r: NodeRef) -> Vec<(NodeRef, EdgeKind)> {
+        let mut out: Vec<(NodeRef, EdgeKind)> = match self.tree(r.tree) {
+            Some(tree) => tree.prerequisites(r.node).map(|e| (NodeRef { tree: r.tree, node: e.from }, e.kind)).collect(),
+            None => Vec::new(),
+        };
+        out.extend(self.links.iter().filter(|l| l.to == r).map(|l| (l.from, l.kind)));
+        out
+    }
+
+    /// True if `node` transitively needs `prerequisite`, following edges and links.
+    pub fn depends_on(&self, node: NodeRef, prerequisite: NodeRef) -> bool {
+        let mut seen = HashSet::from([node]);
+        let mut stack = vec![node];
+        while let Some(current) = stack.pop() {
+            for (p, _) in self.prerequisites(current) {
+                if p == prerequisite {
+                    return true;
+                }
+                if seen.insert(p) {
+                    stack.push(p);
+                }
+            }
+        }
+        false
+    }
+
+    /// States of every node, where a `Requires` link locks a node just like an edge of its own tree.
+    pub fn node_states(&self) -> HashMap<NodeRef, NodeState> {
+        let mut states = HashMap::new();
+        for m in &self.members {
+            for node in &m.tree.nodes {
+                self.resolve_state(NodeRef { tree: m.id, node: node.id }, &mut states);
+            }
+        }
+        states
+    }
+
+    /// States of the nodes of one tree, taking links from other trees into account.
+    pub fn tree_states(&self, id: TreeId) -> HashMap<NodeId, NodeState> {
+        let mut states = HashMap::new();
+        let Some(tree) = self.tree(id) else { return HashMap::new() };
+        tree.nodes
+            .iter()
+            .map(|n| (n.id, self.resolve_state(NodeRef { tree: id, node: n.id }, &mut states)))
+            .collect()
+    }
+
+    /// Same rules as `GTree::resolve_state`, over the whole workspace.
+    fn resolve_state(&self, r: NodeRef, states: &mut HashMap<NodeRef, NodeState>) -> NodeState {
+        if let Some(&state) = states.get(&r) {
+            return state;
+        }
+        states.insert(r, NodeState::Locked);
+        let unlocked = self
+            .prerequisites(r)
+            .into_iter()
+            .filter(|&(p, kind)| kind == EdgeKind::Requires && self.node(p).is_some())
+            .all(|(p, _)| self.resolve_state(p, states) == NodeState::Complete);
+        let progress = self.node(r).map_or(0.0, GNode::progress);
+        let state = if !unlocked {
+            NodeState::Locked
+        } else if progress >= 1.0 {
+            NodeState::Complete
+        } else if progress > 0.0 {
+            NodeState::InProgress
+        } else {
+            NodeState::Available
+        };
+        states.insert(r, state);
+        state
+    }
+
+    /// Weighted progress over every node of every tree, so bigger trees count for more.
+    pub fn progress(&self) -> f32 {
+        weighted_progress(self.members.iter().flat_map(|m| m.tree.nodes.iter().map(|n| (n.weight, n.progress()))))
+    }
+
+    pub fn summaries(&self) -> Vec<TreeSummary> {
+        let states = self.node_states();
+        self.members
+            .iter()
+            .map(|m| TreeSummary {
+                id: m.id,
+                title: m.tree.title.clone(),
+                progress: m.tree.progress(),
+                nodes: m.tree.nodes.len(),
+                complete: m
+                    .tree
+                    .nodes
+                    .iter()
+                    .filter(|n| states.get(&NodeRef { tree: m.id, node: n.id }) == Some(&NodeState::Complete))
+                    .count(),
+                waiting_on: self
+                    .links
+                    .iter()
+                    .filter(|l| l.to.tree == m.id && l.kind == EdgeKind::Requires)
+                    .filter(|l| self.node(l.from).is_some() && states.get(&l.from) != Some(&NodeState::Complete))
+                    .copied()
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Habits due on `today` on the nodes of tree `id` that neither its edges nor links lock.
+    pub fn due_habits(&self, id: TreeId, today: NaiveDate) -> Vec<(NodeId, usize)> {
+        self.tree(id).map_or(Vec::new(), |tree| tree.due_habits_in(today, &self.tree_states(id)))
+    }
+
+    /// Grind mode refuses task work on nodes that links from other trees keep locked,
+    /// which the tree on its own can't see.
+    fn check_links(&self, id: TreeId, mode: Mode, command: &Command) -> Result<(), CommandError> {
+        if mode != Mode::Grind {
+            return Ok(());
+        }
+        let states = self.tree_states(id);
+        match command.task_nodes().into_iter().find(|n| states.get(n) == Some(&NodeState::Locked)) {
+            Some(node) => Err(TaskError::Locked(node).into()),
+            None => Ok(()),
+        }
+    }
+
+    /// `History::execute` on tree `id`, also honouring links.
+    pub fn execute(&mut self, id: TreeId, history: &mut History, mode: Mode, command: Command) -> Result<(), CommandError> {
+        self.check_links(id, mode, &command)?;
+        let tree = self.tree_mut(id).ok_or(CommandError::UnknownTree(id))?;
+        history.execute(tree, mode, command)
+    }
+
+    /// `History::undo` on tree `id`, also honouring links.
+    pub fn undo(&mut self, id: TreeId, history: &mut History, mode: Mode) -> Result<bool, CommandError> {
+        if let Some(command) = history.next_undo() {
+            self.check_links(id, mode, command)?;
+        }
+        let tree = self.tree_mut(id).ok_or(CommandError::UnknownTree(id))?;
+        history.undo(tree, mode)
+    }
+
+    /// `History::redo` on tree `id`, also honouring links.
+    pub fn redo(&mut self, id: TreeId, history: &mut History, mode: Mode) -> Result<bool, CommandError> {
+        if let Some(command) = history.next_redo() {
+            self.check_links(id, mode, command)?;
+        }
+        let tree = self.tree_mut(id).ok_or(CommandError::UnknownTree(id))?;
+        history.redo(tree, mode)
+    }
+
+    /// Problems with the links. Trees are validated on their own when they load.
+    /// Links to nodes that were deleted while a tree was edited by itself are only warnings.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut out = Vec::new();
+        let mut ids = HashSet::new();
+        for (i, m) in self.members.iter().enumerate() {
+            if !ids.insert(m.id) {
+                out.push(Diagnostic::new(Severity::Error, format!("trees[{}].id", i), Problem::DuplicateTree(m.id)));
+            }
+        }
+        let mut seen = HashSet::new();
+        for (i, link) in self.links.iter().enumerate() {
+            let path = format!("links[{}]", i);
+            for (field, r) in [("from", link.from), ("to", link.to)] {
+                if self.tree(r.tree).is_none() {
+                    out.push(Diagnostic::new(Severity::Warning, format!("{}.{}.tree", path, field), Problem::UnknownTree(r.tree)));
+                } else if self.node(r).is_none() {
+                    out.push(Diagnostic::new(
+                        Severity::Warning,
+                        format!("{}.{}.node", path, field),
+                        Problem::DanglingReference(r.node),
+                    ));
+                }
+            }
+            if link.from.tree == link.to.tree {
+                out.push(Diagnostic::new(Severity::Warning, path.clone(), Problem::SameTreeLink));
+            }
+            if self.depends_on(link.from, link.to) {
+                out.push(Diagnostic::new(
+                    Severity::Error,
+                    path.clone(),
+                    Problem::LinkCycle { from: link.from, to: link.to },
+                ));
+            }
+            if !seen.insert((link.from, link.to)) {
+                out.push(Diagnostic::new(
+                    Severity::Warning,
+                    path,
+                    Problem::DuplicateEdge { from: link.from.node, to: link.to.node },
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// What the workspace file holds.
+#[derive(Serialize, Deserialize)]
+struct WorkspaceDoc {
+    version: u64,
+    name: String,
+    trees: Vec<MemberDoc>,
+    #[serde(default)]
+    links: Vec<Link>,
+    /// Missing from files written before ids were retired; the ids in use are enough then.
+    #[serde(default)]
+    next_id: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MemberDoc {
+    id: TreeId,
+    path: PathBuf,
+}
+
+/// Turns a tree title into a file name, e.g. "Learn Rust!" into "learn-rust".
+fn file_stem(title: &str) -> String {
+    let stem: String = title
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let stem = stem.split('-').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("-");
+    if stem.is_empty() { "tree".to_string() } else { stem }
+}
+
+/// Writes the workspace file and every tree. Trees that have no file yet get one next to
+/// the workspace file, named after their title; absolute paths are made relative.
+/// Both are remembered in `Member::path`.
+pub fn save_workspace_to_file(workspace: &mut Workspace, path: &str) -> Result<(), Error> {
+    let base = Path::new(path).parent().unwrap_or(Path::new(""));
+    let mut taken: HashSet<PathBuf> = workspace.members.iter().filter_map(|m| m.path.clone()).collect();
+    for m in &mut workspace.members {
+        if let Some(file) = &m.path
+            && file.is_absolute()
+        {
+            m.path = Some(relative_to(file, &std::path::absolute(base).unwrap_or_else(|_| base.to_path_buf())));
+        }
+        if m.path.is_none() {
+            let stem = file_stem(&m.tree.title);
+            let file = (1..)
+                .map(|n| PathBuf::from(if n == 1 { format!("{}.json", stem) } else { format!("{}-{}.json", stem, n) }))
+                .find(|p| !taken.contains(p))
+                .expect("some name is free");
+            taken.insert(file.clone());
+            // Files attached before the tree had a home are still as picked.
+            m.tree.rebase_resources(None, &base.join(&file));
+            m.path = Some(file);
+        }
+        let file = base.join(m.path.as_ref().expect("assigned above"));
+        save_tree_to_file(&m.tree, &file.to_string_lossy()).map_err(|e| Error::Member { path: file, error: Box::new(e) })?;
+    }
+    let doc = WorkspaceDoc {
+        version: WORKSPACE_VERSION,
+        name: workspace.name.clone(),
+        trees: workspace
+            .members
+            .iter()
+            .map(|m| MemberDoc { id: m.id, path: m.path.clone().expect("assigned above") })
+            .collect(),
+        links: workspace.links.clone(),
+        next_id: workspace.next_id,
+    };
+    fs::write(path, serde_json::to_string_pretty(&doc)?)?;
+    Ok(())
+}
+
+/// Loads a workspace and all of its trees. A tree that fails to load fails the whole workspace,
+/// and so do two trees with the same id.
+pub fn load_workspace_from_file(path: &str) -> Result<Workspace, Error> {
+    let doc: WorkspaceDoc = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if doc.version > WORKSPACE_VERSION {
+        return Err(Error::UnsupportedVersion { found: doc.version, supported: WORKSPACE_VERSION });
+    }
+    let mut ids = HashSet::new();
+    let duplicates: Vec<Diagnostic> = doc
+        .trees
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| !ids.insert(m.id))
+        .map(|(i, m)| Diagnostic::new(Severity::Error, format!("trees[{}].id", i), Problem::DuplicateTree(m.id)))
+        .collect();
+    if !duplicates.is_empty() {
+        return Err(Error::Invalid(duplicates));
+    }
+    let next_id = doc.trees.iter().map(|m| m.id.0 + 1).max().unwrap_or(0).max(doc.next_id);
+    let base = Path::new(path).parent().unwrap_or(Path::new(""));
+    let mut members = Vec::new();
+    for m in doc.trees {
+        let file = base.join(&m.path);
+        let tree = load_tree_from_file(&file.to_string_lossy()).map_err(|e| Error::Member { path: file, error: Box::new(e) })?;
+        members.push(Member { id: m.id, path: Some(m.path), tree });
+    }
+    Ok(Workspace { name: doc.name, members, links: doc.links, next_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::habit::{Habit, Schedule};
+    use crate::resource::{Resource, Target};
+    use crate::skill_tree::{Task, TaskKind};
+
+    /// Tree 0 has a -> b, tree 1 has c -> d; a and c have a task each, and c a daily habit after it.
+    fn sample() -> (Workspace, [NodeRef; 4]) {
+        let mut workspace = Workspace::new("Work");
+        let mut refs = Vec::new();
+        for (title, names) in [("First", ["a", "b"]), ("Second", ["c", "d"])] {
+            let mut tree = GTree::new(title);
+            let mut first = GNode::new(names[0]);
+            first.tasks.push(Task::new("task"));
+            let first = tree.add_node(first);
+            let second = tree.add_child(first, GNode::new(names[1])).unwrap();
+            let id = workspace.add_tree(tree, None);
+            refs.extend([first, second].map(|node| NodeRef { tree: id, node }));
+        }
+        let c = refs[2];
+        let mut habit = Task::new("habit");
+        habit.kind = TaskKind::Habit(Habit::new(Schedule::Daily));
+        workspace.tree_mut(c.tree).unwrap().edit_node(c.node, |n| n.tasks.push(habit));
+        (workspace, [refs[0], refs[1], refs[2], refs[3]])
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("grind_trees_workspace_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn add_link_rejects_bad_links() {
+        let (mut workspace, [a, b, c, d]) = sample();
+        let missing = NodeRef { tree: c.tree, node: NodeId(99) };
+        assert_eq!(workspace.add_link(a, missing, EdgeKind::Requires), Err(LinkError::UnknownNode(missing)));
+        assert_eq!(workspace.add_link(a, b, EdgeKind::Requires), Err(LinkError::SameTree(a.tree)));
+
+        workspace.add_link(b, c, EdgeKind::Requires).unwrap();
+        assert_eq!(workspace.add_link(b, c, EdgeKind::Recommends), Err(LinkError::Duplicate { from: b, to: c }));
+        // d needs c needs b needs a, through both trees.
+        assert_eq!(workspace.add_link(d, a, EdgeKind::Recommends), Err(LinkError::Cycle { from: d, to: a }));
+        assert_eq!(workspace.links.len(), 1);
+        assert!(workspace.depends_on(d, a));
+        assert!(!workspace.depends_on(a, d));
+    }
+
+    #[test]
+    fn link_cycle_finds_cycles_closed_by_tree_edges() {
+        let (mut workspace, [a, b, c, d]) = sample();
+        workspace.add_link(b, c, EdgeKind::Requires).unwrap();
+        workspace.add_link(d, a, EdgeKind::Recommends).unwrap_err();
+        // Drop d's edge to c, link d back to a, then put the edge back from inside the tree.
+        workspace.tree_mut(d.tree).unwrap().remove_edge(c.node, d.node).unwrap();
+        workspace.add_link(d, a, EdgeKind::Recommends).unwrap();
+        assert_eq!(workspace.link_cycle(), None);
+        workspace.tree_mut(d.tree).unwrap().add_edge(c.node, d.node, EdgeKind::Requires).unwrap();
+        assert!(workspace.link_cycle().is_some());
+        assert!(workspace.validate().iter().any(|d| matches!(d.problem, Problem::LinkCycle { .. })));
+    }
+
+    #[test]
+    fn requires_links_lock_across_trees() {
+        let (mut workspace, [a, _, c, d]) = sample();
+        workspace.add_link(a, c, EdgeKind::Requires).unwrap();
+        let states = workspace.tree_states(c.tree);
+        assert_eq!(states[&c.node], NodeState::Locked);
+        assert_eq!(states[&d.node], NodeState::Locked);
+        // The tree on its own doesn't know.
+        assert_eq!(workspace.tree(c.tree).unwrap().node_state(c.node), NodeState::Available);
+
+        workspace.tree_mut(a.tree).unwrap().set_task_checked(Mode::Grind, a.node, 0, true).unwrap();
+        assert_eq!(workspace.tree_states(c.tree)[&c.node], NodeState::Available);
+
+        let (mut workspace, [a, _, c, _]) = sample();
+        workspace.add_link(a, c, EdgeKind::Recommends).unwrap();
+        assert_eq!(workspace.tree_states(c.tree)[&c.node], NodeState::Available);
+    }
+
+    #[test]
+    fn grind_work_and_due_habits_honour_links() {
+        let (mut workspace, [a, b, c, _]) = sample();
+        workspace.add_link(b, c, EdgeKind::Requires).unwrap();
+        let today = crate::habit::today();
+        let mut history = History::new();
+        let toggle = Command::ToggleTask { node: c.node, task: 0 };
+        assert_eq!(
+            workspace.execute(c.tree, &mut history, Mode::Grind, toggle.clone()),
+            Err(CommandError::Task(TaskError::Locked(c.node)))
+        );
+        assert!(workspace.due_habits(c.tree, today).is_empty());
+        assert_eq!(workspace.tree(c.tree).unwrap().due_habits(today), [(c.node, 1)]);
+
+        // Finish a and b, and c unlocks.
+        let first = workspace.tree_mut(a.tree).unwrap();
+        first.set_task_checked(Mode::Grind, a.node, 0, true).unwrap();
+        first.edit_node(b.node, |n| n.tasks.push(Task::new("done")));
+        first.set_task_checked(Mode::Grind, b.node, 0, true).unwrap();
+        workspace.execute(c.tree, &mut history, Mode::Grind, toggle).unwrap();
+        assert_eq!(workspace.due_habits(c.tree, today), [(c.node, 1)]);
+
+        // Once b is open again, c's work can't be undone or redone in grind mode.
+        workspace.tree_mut(b.tree).unwrap().set_task_checked(Mode::Grind, b.node, 0, false).unwrap();
+        let locked = Err(CommandError::Task(TaskError::Locked(c.node)));
+        assert_eq!(workspace.undo(c.tree, &mut history, Mode::Grind), locked);
+        assert_eq!(workspace.undo(c.tree, &mut history, Mode::Edit), Ok(true));
+        assert_eq!(workspace.redo(c.tree, &mut history, Mode::Grind), locked);
+        assert_eq!(workspace.undo(TreeId(9), &mut history, Mode::Edit), Err(CommandError::UnknownTree(TreeId(9))));
+    }
+
+    #[test]
+    fn removed_tree_ids_are_retired() {
+        let (mut workspace, [_, _, c, _]) = sample();
+        workspace.add_link(NodeRef { tree: TreeId(0), node: NodeId(0) }, c, EdgeKind::Requires).unwrap();
+        assert!(workspace.remove_tree(c.tree).is_some());
+        assert!(workspace.links.is_empty());
+        assert_eq!(workspace.add_tree(GTree::new("Third"), None), TreeId(2));
+        workspace.remove_tree(TreeId(2));
+
+        let dir = temp_dir("retired");
+        let path = dir.join("work.json").to_string_lossy().into_owned();
+        save_workspace_to_file(&mut workspace, &path).unwrap();
+        let mut loaded = load_workspace_from_file(&path).unwrap();
+        assert_eq!(loaded.add_tree(GTree::new("Fourth"), None), TreeId(3));
+    }
+
+    #[test]
+    fn save_and_load_keep_member_paths_relative() {
+        let (mut workspace, [a, b, c, _]) = sample();
+        workspace.add_link(b, c, EdgeKind::Requires).unwrap();
+        let dir = temp_dir("round_trip");
+        fs::create_dir_all(dir.join("trees")).unwrap();
+        workspace.members[1].path = Some(dir.join("trees").join("second.json"));
+        // Attached before the first tree had a file, so still as picked.
+        let attached = dir.join("notes").join("guide.md");
+        let resource = Resource::file(&attached, None);
+        workspace.tree_mut(a.tree).unwrap().edit_node(a.node, |n| n.resources.push(resource));
+
+        let path = dir.join("work.json").to_string_lossy().into_owned();
+        save_workspace_to_file(&mut workspace, &path).unwrap();
+        assert_eq!(workspace.members[0].path, Some(PathBuf::from("first.json")));
+        assert_eq!(workspace.members[1].path, Some(PathBuf::from("trees/second.json")));
+
+        let loaded = load_workspace_from_file(&path).unwrap();
+        assert_eq!(loaded.name, "Work");
+        assert_eq!(loaded.links, workspace.links);
+        let paths: Vec<_> = loaded.members.iter().map(|m| (m.id, m.path.clone())).collect();
+        let saved: Vec<_> = workspace.members.iter().map(|m| (m.id, m.path.clone())).collect();
+        assert_eq!(paths, saved);
+        let resource = &loaded.node(a).unwrap().resources[0];
+        assert_eq!(resource.target, Target::Path(PathBuf::from("notes/guide.md")));
+        let member_file = dir.join("first.json");
+        assert_eq!(resource.location(Some(&member_file)), attached.display().to_string());
+    }
+}
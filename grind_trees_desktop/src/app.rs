@@ -1,4 +1,11 @@
-use core::skill_tree::GTree;
+use core::achievement::Achievement;
+use core::command::{Command, CommandError, History};
+use core::skill_tree::{GTree, Mode, NodeId, TaskKind};
+use core::tags::TagFilter;
+use core::template::{Template, TemplateError};
+use core::workspace::{LinkError, TreeId, Workspace};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 use macroquad::prelude::*;
 
@@ -16,8 +23,45 @@ pub struct AppState {
     pub save: bool,
     pub quit: bool,
     pub file: Option<String>,
+    /// The tree on screen when no workspace is open.
     pub skill_tree: Option<GTree>,
+    /// Undo/redo for every change made to the current tree.
+    pub history: History,
+    pub mode: Mode,
+    /// Last load/save outcome, shown at the bottom of the screen.
+    pub message: Option<String>,
+    /// Nodes not matching this are dimmed.
+    pub tag_filter: TagFilter,
+    pub new_from_template: bool,
+    pub import_branch: bool,
+    /// A tree picked by "Import branch from file…", waiting for the branch to take and the
+    /// node to graft it under.
+    pub pending_graft: Option<PendingGraft>,
+    pub open_workspace: bool,
+    pub add_to_workspace: bool,
+    /// While open, the trees live here instead of in `skill_tree`.
+    pub workspace: Option<Workspace>,
+    pub workspace_file: Option<String>,
+    /// The workspace tree on screen; `None` shows the dashboard.
+    pub current_tree: Option<TreeId>,
+    /// Questions asked before a template becomes the current tree.
+    pub template_form: Option<TemplateForm>,
+    /// A reading being typed in for a measured task.
+    pub value_form: Option<ValueForm>,
+    /// The achievements gallery is open.
+    pub show_achievements: bool,
+    /// Recently unlocked achievements, with the time their pop-up appeared.
+    pub unlocks: Vec<(&'static Achievement, f64)>,
 }
+/// A tree picked by "Import branch from file…" on its way into the current tree.
+pub struct PendingGraft {
+    pub source: GTree,
+    /// Where `source` was loaded from, which its attached files are relative to.
+    pub path: PathBuf,
+    /// The branch to import; `None` while the user is still choosing it.
+    pub roots: Option<Vec<NodeId>>,
+}
+
 impl AppState {
     pub fn new() -> Self {
         Self {
@@ -32,6 +76,206 @@ impl AppState {
             quit: false,
             file: None,
             skill_tree: None,
+            history: History::new(),
+            mode: Mode::Edit,
+            message: None,
+            tag_filter: TagFilter::new(),
+            new_from_template: false,
+            import_branch: false,
+            pending_graft: None,
+            open_workspace: false,
+            add_to_workspace: false,
+            workspace: None,
+            workspace_file: None,
+            current_tree: None,
+            template_form: None,
+            value_form: None,
+            show_achievements: false,
+            unlocks: Vec::new(),
+        }
+    }
+}
+
+impl AppState {
+    pub fn tree_mut(&mut self) -> Option<&mut GTree> {
+        current_tree(&mut self.skill_tree, &mut self.workspace, self.current_tree)
+    }
+
+    /// Runs `command` on the tree on screen and makes it undoable. In a workspace, links from
+    /// other trees lock nodes too. Does nothing without a tree on screen.
+    pub fn execute(&mut self, command: Command) -> Result<(), CommandError> {
+        match (&mut self.workspace, self.current_tree, &mut self.skill_tree) {
+            (Some(workspace), Some(id), _) => workspace.execute(id, &mut self.history, self.mode, command),
+            (None, _, Some(tree)) => self.history.execute(tree, self.mode, command),
+            _ => Ok(()),
         }
     }
+
+    /// Undoes the last change to the tree on screen, or redoes the last undone one, honouring
+    /// links like `execute`. Returns `Ok(false)` if there was nothing to take.
+    pub fn step_history(&mut self, redo: bool) -> Result<bool, CommandError> {
+        match (&mut self.workspace, self.current_tree, &mut self.skill_tree) {
+            (Some(workspace), Some(id), _) if redo => workspace.redo(id, &mut self.history, self.mode),
+            (Some(workspace), Some(id), _) => workspace.undo(id, &mut self.history, self.mode),
+            (None, _, Some(tree)) if redo => self.history.redo(tree, self.mode),
+            (None, _, Some(tree)) => self.history.undo(tree, self.mode),
+            _ => Ok(false),
+        }
+    }
+
+    /// Awards what the current tree has earned and queues a pop-up for each new achievement.
+    pub fn check_achievements(&mut self) {
+        let Some(tree) = self.tree_mut() else { return };
+        for achievement in tree.check_achievements() {
+            println!("Achievement unlocked: {}", achievement.name);
+            self.unlocks.push((achievement, get_time()));
+        }
+    }
+
+    /// Whether the open workspace has a prerequisite cycle across trees; see `revert_link_cycle`.
+    pub fn has_link_cycle(&self) -> bool {
+        self.workspace.as_ref().is_some_and(|w| w.link_cycle().is_some())
+    }
+
+    /// Checks the workspace after the last history step on one of its trees and takes the step
+    /// back if it closed a prerequisite cycle across trees. `undone` says whether the step was
+    /// an undo; a cycle that was there before the step (`had_cycle`) is left to the user.
+    /// Returns true if the step was taken back.
+    pub fn revert_link_cycle(&mut self, undone: bool, had_cycle: bool) -> bool {
+        let (Some(workspace), Some(id)) = (&mut self.workspace, self.current_tree) else { return false };
+        let Some(link) = workspace.link_cycle().filter(|_| !had_cycle) else { return false };
+        let Some(tree) = workspace.tree_mut(id) else { return false };
+        let reverted = if undone { self.history.redo(tree, self.mode) } else { self.history.undo(tree, self.mode) };
+        if let Err(e) = reverted {
+            println!("Couldn't take back the change: {}", e);
+        }
+        self.message = Some(format!("Can't do that: {}", LinkError::Cycle { from: link.from, to: link.to }));
+        true
+    }
+
+    /// Shows a single tree, closing any open workspace.
+    pub fn open_tree(&mut self, tree: GTree, file: Option<String>) {
+        self.workspace = None;
+        self.workspace_file = None;
+        self.current_tree = None;
+        self.skill_tree = Some(tree);
+        self.file = file;
+        self.history.clear();
+        self.tag_filter = TagFilter::new();
+    }
+
+    /// Shows another tree of the workspace, or the dashboard for `None`.
+    /// Undo history is per tree on screen and starts over.
+    pub fn switch_tree(&mut self, id: Option<TreeId>) {
+        self.current_tree = id;
+        self.file = id.and_then(|id| self.member_file(id));
+        self.history.clear();
+        self.tag_filter = TagFilter::new();
+    }
+
+    /// Where a workspace tree is saved, which is also what its attached files are relative to.
+    pub fn member_file(&self, id: TreeId) -> Option<String> {
+        let path = self.workspace.as_ref()?.member(id)?.path.as_ref()?;
+        let base = self.workspace_file.as_deref().and_then(|f| Path::new(f).parent()).unwrap_or(Path::new(""));
+        Some(base.join(path).to_string_lossy().into_owned())
+    }
+}
+
+/// The tree being worked on: the workspace's current tree while one is open, otherwise the
+/// single loaded tree. Takes the fields separately so the rest of the state stays borrowable.
+pub fn current_tree<'a>(
+    skill_tree: &'a mut Option<GTree>,
+    workspace: &'a mut Option<Workspace>,
+    current: Option<TreeId>,
+) -> Option<&'a mut GTree> {
+    match workspace {
+        Some(ws) => ws.tree_mut(current?),
+        None => skill_tree.as_mut(),
+    }
+}
+
+/// Asks for a new reading of a measured task, submitted as `Command::SetTaskValue`.
+pub struct ValueForm {
+    pub node: NodeId,
+    pub task: usize,
+    /// The task and its unit, shown above the field.
+    pub prompt: String,
+    /// The reading so far, used when the input is left empty.
+    pub current: f32,
+    pub input: String,
+}
+
+impl ValueForm {
+    /// `None` unless the task is measured.
+    pub fn new(tree: &GTree, node: NodeId, task: usize) -> Option<Self> {
+        let task_ref = tree.node(node)?.tasks.get(task)?;
+        let TaskKind::Measure(m) = &task_ref.kind else { return None };
+        let prompt = format!("{} (target {} {})", task_ref.content, m.target, m.unit);
+        Some(Self { node, task, prompt, current: m.current, input: String::new() })
+    }
+
+    /// The typed reading, or the current one when nothing was typed.
+    pub fn value(&self) -> Result<f32, String> {
+        let input = self.input.trim();
+        if input.is_empty() {
+            return Ok(self.current);
+        }
+        match input.replace(',', ".").parse::<f32>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("\"{}\" isn't a number", input)),
+        }
+    }
+}
+
+/// Walks through a template's parameters, then asks whether to keep each optional node.
+pub struct TemplateForm {
+    pub template: Template,
+    /// Index of the current question.
+    pub step: usize,
+    /// What has been typed for the current question.
+    pub input: String,
+    pub values: HashMap<String, String>,
+    pub skip: HashSet<NodeId>,
+}
+
+impl TemplateForm {
+    pub fn new(template: Template) -> Self {
+        Self { template, step: 0, input: String::new(), values: HashMap::new(), skip: HashSet::new() }
+    }
+
+    /// The current question and the answer used when the input is left empty.
+    pub fn question(&self) -> Option<(String, String)> {
+        let params = &self.template.params;
+        if let Some(param) = params.get(self.step) {
+            return Some((param.prompt().to_string(), param.default.clone().unwrap_or_default()));
+        }
+        let id = *self.template.optional.get(self.step - params.len())?;
+        let title = self.template.node_title(id).unwrap_or("?");
+        Some((format!("Include \"{}\"? (y/n)", title), "y".to_string()))
+    }
+
+    /// Takes the input as the answer to the current question and moves on.
+    pub fn answer(&mut self) {
+        let params = self.template.params.len();
+        let input = std::mem::take(&mut self.input);
+        let input = input.trim();
+        if self.step < params {
+            if !input.is_empty() {
+                self.values.insert(self.template.params[self.step].name.clone(), input.to_string());
+            }
+        } else if let Some(&id) = self.template.optional.get(self.step - params)
+            && input.to_lowercase().starts_with('n')
+        {
+            self.skip.insert(id);
+        }
+        self.step += 1;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.step >= self.template.params.len() + self.template.optional.len()
+    }
+
+    pub fn instantiate(&self) -> Result<GTree, TemplateError> {
+        self.template.instantiate(&self.values, &self.skip)
+    }
 }
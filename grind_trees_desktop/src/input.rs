@@ -1,12 +1,37 @@
-use crate::app::{self, AppState};
+use crate::app::{self, AppState, PendingGraft, TemplateForm};
 use macroquad::prelude::*;
 use rfd::FileDialog;
-use core::skill_tree::{save_tree_to_file, load_tree_from_file};
+use core::error::Error;
+use core::command::Command;
+use core::skill_tree::{save_tree_to_file, load_tree_from_file, GTree, Mode, NodeId};
+use core::template::load_template_from_file;
+use core::workspace::{load_workspace_from_file, save_workspace_to_file, Workspace};
+use std::path::Path;
 
 pub fn handle_input(state: &mut AppState) {
     let mouse = mouse_position();
     state.mouse_pos = mouse;
 
+    // The template form takes all typing until it is answered or dismissed.
+    if state.template_form.is_some() {
+        handle_template_form(state);
+        return;
+    }
+    if state.value_form.is_some() {
+        handle_value_form(state);
+        return;
+    }
+    if let Some(pending) = &state.pending_graft {
+        if is_key_pressed(KeyCode::Escape) {
+            state.pending_graft = None;
+            state.message = Some("Import cancelled".to_string());
+            return;
+        }
+        if pending.roots.is_some() && (is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter)) {
+            import_branch(state, None);
+        }
+    }
+
     if is_mouse_button_pressed(MouseButton::Left) {
         println!("Clicked at: {:?}", mouse);
     }
@@ -30,6 +55,9 @@ pub fn handle_input(state: &mut AppState) {
         state.pan_speed = app::DEFAULT_PAN_SPEED;
     }
     if is_key_down(KeyCode::LeftControl) {
+        if is_key_pressed(KeyCode::Z) {
+            handle_undo(state, is_key_down(KeyCode::LeftShift));
+        }
         if is_key_down(KeyCode::Equal) {
             state.zoom *= 1. + state.zoom_speed;
         }
@@ -37,12 +65,142 @@ pub fn handle_input(state: &mut AppState) {
             state.zoom *= 1. - state.zoom_speed;
         }
     }
-    if is_key_pressed(KeyCode::Escape) {
+    if is_key_pressed(KeyCode::Escape) && state.show_achievements {
+        state.show_achievements = false;
+    } else if is_key_pressed(KeyCode::Escape) {
         state.menu_on = !state.menu_on;
     }
+    if is_key_pressed(KeyCode::Tab) {
+        state.mode = match state.mode {
+            Mode::Edit => Mode::Grind,
+            Mode::Grind => Mode::Edit,
+        };
+    }
     handle_menu_input(state);
 }
 
+/// Typing answers the current question, Enter moves on, Escape gives up.
+fn handle_template_form(state: &mut AppState) {
+    let Some(form) = &mut state.template_form else { return };
+    while let Some(c) = get_char_pressed() {
+        if !c.is_control() {
+            form.input.push(c);
+        }
+    }
+    if is_key_pressed(KeyCode::Backspace) {
+        form.input.pop();
+    }
+    if is_key_pressed(KeyCode::Escape) {
+        state.template_form = None;
+        state.message = Some("Cancelled new tree".to_string());
+        return;
+    }
+    if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+        form.answer();
+    }
+    finish_template_form(state);
+}
+
+/// Creates the tree once every question is answered.
+fn finish_template_form(state: &mut AppState) {
+    let Some(form) = state.template_form.take_if(|f| f.is_done()) else { return };
+    match form.instantiate() {
+        Ok(tree) => {
+            state.message = Some(format!("Created \"{}\" from template \"{}\"", tree.title, form.template.name));
+            // A new tree has no file yet; saving asks for one.
+            state.open_tree(tree, None);
+        }
+        Err(e) => {
+            println!("Failed to instantiate template: {}", e);
+            state.message = Some(format!("Couldn't use template: {}", e));
+        }
+    }
+}
+
+/// Typing enters the reading, Enter sets it, Escape leaves the task as it was.
+fn handle_value_form(state: &mut AppState) {
+    let Some(form) = &mut state.value_form else { return };
+    while let Some(c) = get_char_pressed() {
+        if !c.is_control() {
+            form.input.push(c);
+        }
+    }
+    if is_key_pressed(KeyCode::Backspace) {
+        form.input.pop();
+    }
+    if is_key_pressed(KeyCode::Escape) {
+        state.value_form = None;
+        return;
+    }
+    if !(is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter)) {
+        return;
+    }
+    let value = match form.value() {
+        Ok(value) => value,
+        Err(e) => {
+            state.message = Some(e);
+            form.input.clear();
+            return;
+        }
+    };
+    let (node, task) = (form.node, form.task);
+    state.value_form = None;
+    match state.execute(Command::SetTaskValue { node, task, value }) {
+        Ok(()) => state.check_achievements(),
+        Err(e) => state.message = Some(format!("Can't change task: {}", e)),
+    }
+}
+
+/// Takes `roots` as the branch of the pending tree to import and asks where it goes.
+pub fn pick_branch(state: &mut AppState, roots: Vec<NodeId>) {
+    let Some(pending) = &mut state.pending_graft else { return };
+    let what = match roots.as_slice() {
+        [root] => pending.source.node(*root).map_or(String::new(), |n| n.title.clone()),
+        _ => pending.source.title.clone(),
+    };
+    state.message = Some(format!(
+        "Click the node to import \"{}\" under, Enter to add it at the top level, Esc to cancel",
+        what
+    ));
+    pending.roots = Some(roots);
+}
+
+/// Grafts the picked branch of the pending tree under `under`, or as new roots when `None`.
+/// Attached files are rebased from the source file onto the current tree's.
+pub fn import_branch(state: &mut AppState, under: Option<NodeId>) {
+    let Some(PendingGraft { mut source, path, roots: Some(roots) }) = state.pending_graft.take() else { return };
+    match state.file.as_deref() {
+        Some(file) => source.rebase_resources(Some(&path), Path::new(file)),
+        None => source.resolve_resources(&path),
+    }
+    let had_cycle = state.has_link_cycle();
+    let Some(count) = state.tree_mut().map(|t| t.nodes.len()) else { return };
+    let title = source.title.clone();
+    match state.execute(Command::Graft { source, roots, under }) {
+        Ok(()) => {
+            let added = state.tree_mut().map_or(0, |t| t.nodes.len() - count);
+            state.message = Some(format!("Imported {} node(s) from \"{}\"", added, title));
+            state.revert_link_cycle(false, had_cycle);
+        }
+        Err(e) => state.message = Some(format!("Can't import \"{}\": {}", title, e)),
+    }
+}
+
+/// Ctrl+Z undoes the last change, Ctrl+Shift+Z redoes it.
+fn handle_undo(state: &mut AppState, redo: bool) {
+    let had_cycle = state.has_link_cycle();
+    match state.step_history(redo) {
+        // Redoing work can earn what undoing it couldn't take away.
+        Ok(true) => {
+            if !state.revert_link_cycle(!redo, had_cycle) {
+                state.check_achievements();
+            }
+        }
+        Ok(false) => state.message = Some(format!("Nothing to {}", if redo { "redo" } else { "undo" })),
+        Err(e) => state.message = Some(format!("Can't {}: {}", if redo { "redo" } else { "undo" }, e)),
+    }
+}
+
 fn handle_menu_input(state: &mut AppState) {
     if state.load {
         state.load = false;
@@ -56,16 +214,122 @@ fn handle_menu_input(state: &mut AppState) {
             match load_tree_from_file(path.to_str().unwrap()) {
                 Ok(tree) => {
                     println!("Successfully loaded skill tree: {:?}", tree.title);
-                    state.skill_tree = Some(tree);
-                    state.file = Some(path.to_string_lossy().into_owned());
+                    for diagnostic in tree.validate() {
+                        println!("{}", diagnostic);
+                    }
+                    state.message = Some(format!("Loaded \"{}\"", tree.title));
+                    state.open_tree(tree, Some(path.to_string_lossy().into_owned()));
                 }
                 Err(e) => {
                     println!("Failed to load skill tree: {}", e);
+                    state.message = Some(load_error_message(&e));
+                }
+            }
+        }
+    }
+
+    if state.new_from_template {
+        state.new_from_template = false;
+
+        if let Some(path) = FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_title("New from template")
+            .pick_file()
+        {
+            match load_template_from_file(path.to_str().unwrap()) {
+                Ok(template) => {
+                    println!("Loaded template: {:?}", template.name);
+                    state.menu_on = false;
+                    // Flush keys typed before the form opened.
+                    while get_char_pressed().is_some() {}
+                    state.template_form = Some(TemplateForm::new(template));
+                    finish_template_form(state);
+                }
+                Err(e) => {
+                    println!("Failed to load template: {}", e);
+                    state.message = Some(format!("Couldn't load template: {}", e));
                 }
             }
         }
     }
 
+    if state.import_branch {
+        state.import_branch = false;
+
+        if state.tree_mut().is_none() {
+            state.message = Some("Load or create a tree to import into first".to_string());
+        } else if let Some(path) = FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_title("Import branch from file")
+            .pick_file()
+        {
+            match load_tree_from_file(path.to_str().unwrap()) {
+                Ok(source) => {
+                    println!("Picked branch source: {:?}", source.title);
+                    state.menu_on = false;
+                    state.message = Some(format!("Pick the branch of \"{}\" to import, Esc to cancel", source.title));
+                    state.pending_graft = Some(PendingGraft { source, path, roots: None });
+                }
+                Err(e) => {
+                    println!("Failed to load branch: {}", e);
+                    state.message = Some(load_error_message(&e));
+                }
+            }
+        }
+    }
+
+    if state.open_workspace {
+        state.open_workspace = false;
+
+        if let Some(path) = FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_title("Open workspace")
+            .pick_file()
+        {
+            match load_workspace_from_file(path.to_str().unwrap()) {
+                Ok(workspace) => {
+                    println!("Loaded workspace: {:?}", workspace.name);
+                    for diagnostic in workspace.validate() {
+                        println!("{}", diagnostic);
+                    }
+                    state.message = Some(format!("Opened workspace \"{}\"", workspace.name));
+                    let first = workspace.members.first().map(|m| m.id);
+                    state.skill_tree = None;
+                    state.workspace = Some(workspace);
+                    state.workspace_file = Some(path.to_string_lossy().into_owned());
+                    state.switch_tree(first);
+                }
+                Err(e) => {
+                    println!("Failed to load workspace: {}", e);
+                    state.message = Some(load_error_message(&e));
+                }
+            }
+        }
+    }
+
+    if state.add_to_workspace {
+        state.add_to_workspace = false;
+
+        if let Some(path) = FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_title("Add tree to workspace")
+            .pick_file()
+        {
+            match load_tree_from_file(path.to_str().unwrap()) {
+                Ok(tree) => add_to_workspace(state, tree, &path),
+                Err(e) => {
+                    println!("Failed to load skill tree: {}", e);
+                    state.message = Some(load_error_message(&e));
+                }
+            }
+        }
+    }
+
+    if state.save && state.workspace.is_some() {
+        state.save = false;
+        save_workspace(state);
+    }
+
     if state.save {
         state.save = false;
 
@@ -82,11 +346,19 @@ fn handle_menu_input(state: &mut AppState) {
         if let Some(path) = path {
             println!("Saving to file: {}", path);
 
-            if let Some(tree) = &state.skill_tree {
-                if let Err(e) = save_tree_to_file(tree, &path) {
+            if let Some(tree) = &mut state.skill_tree {
+                // Attached files are rebased onto the new file, but only kept once it is written.
+                let mut saved = tree.clone();
+                if state.file.as_deref() != Some(path.as_str()) {
+                    saved.rebase_resources(state.file.as_deref().map(Path::new), Path::new(&path));
+                }
+                if let Err(e) = save_tree_to_file(&saved, &path) {
                     println!("Failed to save file: {}", e);
+                    state.message = Some(format!("Couldn't save: {}", e));
                 } else {
                     println!("Saved!");
+                    *tree = saved;
+                    state.message = Some(format!("Saved to {}", path));
                     state.file = Some(path);
                 }
             } else {
@@ -96,7 +368,81 @@ fn handle_menu_input(state: &mut AppState) {
 
     }
 
+    // The gallery replaces the menu it was opened from.
+    if state.show_achievements {
+        state.menu_on = false;
+    }
+
     if state.quit {
         std::process::exit(0);
     }
 }
+
+/// Adds a tree loaded from `path` to the open workspace. Without one, starts a workspace
+/// holding the tree that was on screen and the new one.
+fn add_to_workspace(state: &mut AppState, tree: GTree, path: &Path) {
+    let title = tree.title.clone();
+    // Absolute until the workspace is saved, then relative to the workspace file.
+    let file = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    if state.workspace.is_none() {
+        let mut workspace = Workspace::new("Workspace");
+        let current = state
+            .skill_tree
+            .take()
+            .map(|t| workspace.add_tree(t, state.file.as_deref().map(|f| std::path::absolute(f).unwrap_or_else(|_| f.into()))));
+        state.workspace = Some(workspace);
+        state.current_tree = current;
+    }
+    let Some(workspace) = &mut state.workspace else { return };
+    let id = workspace.add_tree(tree, Some(file));
+    state.message = Some(format!("Added \"{}\" to the workspace", title));
+    if state.current_tree.is_none() {
+        state.switch_tree(Some(id));
+    }
+}
+
+/// Saves the workspace file and every tree in it, asking where to put the workspace file the first time.
+fn save_workspace(state: &mut AppState) {
+    let path = state.workspace_file.clone().or_else(|| {
+        FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_file_name("workspace.json")
+            .save_file()
+            .map(|p| p.to_string_lossy().into_owned())
+    });
+    let (Some(path), Some(workspace)) = (path, &mut state.workspace) else { return };
+    println!("Saving workspace to: {}", path);
+    match save_workspace_to_file(workspace, &path) {
+        Ok(()) => {
+            println!("Saved!");
+            state.message = Some(format!("Saved workspace to {}", path));
+            state.workspace_file = Some(path);
+            // New trees just got their files.
+            state.file = state.current_tree.and_then(|id| state.member_file(id));
+        }
+        Err(e) => {
+            println!("Failed to save workspace: {}", e);
+            state.message = Some(format!("Couldn't save: {}", e));
+        }
+    }
+}
+
+/// One line for the status bar; the full error has already gone to stdout.
+fn load_error_message(e: &Error) -> String {
+    match e {
+        Error::Io(e) => format!("Couldn't read the file: {}", e),
+        Error::Parse { line: 0, message, .. } => format!("Not a skill tree file: {}", message),
+        Error::Parse { line, column, message } => {
+            format!("Not a skill tree file ({}:{}): {}", line, column, message)
+        }
+        Error::UnsupportedVersion { found, .. } => {
+            format!("This file uses format version {}, please update the app", found)
+        }
+        Error::Invalid(diagnostics) => format!(
+            "The tree is broken, {} problem(s), first: {}",
+            diagnostics.len(),
+            diagnostics.first().map(|d| d.to_string()).unwrap_or_default()
+        ),
+        Error::Member { path, error } => format!("{}: {}", path.display(), load_error_message(error)),
+    }
+}
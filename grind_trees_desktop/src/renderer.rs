@@ -1,6 +1,18 @@
-use crate::app::AppState;
+use crate::app::{self, AppState, TemplateForm, ValueForm};
+use crate::input;
+use markdown_view::{draw_markdown, layout_markdown, open_location};
 use macroquad::prelude::*;
-use core::skill_tree::{GTree, GNode};
+use chrono::NaiveDate;
+use core::achievement::ACHIEVEMENTS;
+use core::command::{Command, CommandError};
+use core::habit::{self, Schedule};
+use core::resource::Resource;
+use core::skill_tree::{GTree, GNode, Mode, NodeId, NodeState, Task, TaskError, TaskKind};
+use core::tags::{Rgb, TagFilter};
+use core::workspace::{NodeRef, TreeId, Workspace};
+use rfd::FileDialog;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
 fn update_camera(state: &AppState) -> Camera2D {
     Camera2D {
@@ -15,7 +27,7 @@ fn update_camera(state: &AppState) -> Camera2D {
 
 pub fn draw(state: &mut AppState) {
     // world level drawing
-    set_camera(&update_camera(&state));
+    set_camera(&update_camera(state));
     draw_circle(0.0, 0.0, 20.0, SKYBLUE);
     // ui level drawing
     set_default_camera();
@@ -29,14 +41,125 @@ pub fn draw(state: &mut AppState) {
         30.0,
         DARKGRAY,
     );
+    draw_text(
+        match state.mode {
+            Mode::Edit => "Edit mode (Tab to grind)",
+            Mode::Grind => "Grind mode (Tab to edit)",
+        },
+        20.0,
+        70.0,
+        24.0,
+        DARKGRAY,
+    );
+    // Clicks belong to the menu, a form, the gallery or the branch picker while one is open.
+    let choosing_branch = state.pending_graft.as_ref().is_some_and(|p| p.roots.is_none());
+    let modal = state.menu_on
+        || state.template_form.is_some()
+        || state.value_form.is_some()
+        || state.show_achievements
+        || choosing_branch;
+    if let Some(workspace) = &state.workspace {
+        let tab = draw_workspace_tabs(workspace, state.current_tree);
+        let row = if state.current_tree.is_none() { draw_dashboard(workspace) } else { None };
+        if let (Some(id), false) = (tab.or(row), modal) {
+            state.switch_tree(id);
+        }
+    }
+    // Within a workspace, links from other trees can lock nodes too.
+    let today = habit::today();
+    let linked = match (&state.workspace, state.current_tree) {
+        (Some(workspace), Some(id)) => Some((workspace.tree_states(id), workspace.due_habits(id, today))),
+        _ => None,
+    };
+    let mut worked = false;
+    if let Some(tree) = app::current_tree(&mut state.skill_tree, &mut state.workspace, state.current_tree) {
+        let (states, due) = linked.unwrap_or_else(|| (tree.node_states(), tree.due_habits(today)));
+        let due = draw_due_today(tree, &due, today).map(|(node, click)| (node, NodeClick::Task(click)));
+        let picking = state.pending_graft.as_ref().is_some_and(|p| p.roots.is_some());
+        let clicked = draw_skill_tree(tree, &states, &state.tag_filter, state.mode, picking).or(due);
+        if let (Some(tag), false) = (draw_tag_filter(tree, &state.tag_filter), modal) {
+            state.tag_filter.toggle(&tag);
+        }
+        match (clicked, modal) {
+            (Some((node, NodeClick::Task(TaskClick::Enter(task)))), false) => {
+                state.value_form = ValueForm::new(tree, node, task);
+            }
+            (Some((node, NodeClick::Task(click))), false) => {
+                // Locks, including those from other trees, are checked as the command runs.
+                let result = task_command(tree, node, click, today);
+                match result.and_then(|command| state.execute(command)) {
+                    Ok(()) => worked = true,
+                    Err(e) => state.message = Some(format!("Can't change task: {}", e)),
+                }
+            }
+            (Some((node, NodeClick::OpenResource(i))), false) => {
+                if let Some(resource) = tree.node(node).and_then(|n| n.resources.get(i)) {
+                    open_location(&resource.location(state.file.as_deref().map(Path::new)));
+                }
+            }
+            (Some((_, NodeClick::OpenLink(url))), false) => open_location(&url),
+            (Some((node, NodeClick::AttachFile)), false) => state.message = attach_file(state, node),
+            (Some((node, NodeClick::Pick)), false) => input::import_branch(state, Some(node)),
+            _ => {}
+        }
+    }
+    if worked {
+        state.check_achievements();
+    }
+    if let Some(pending) = state.pending_graft.as_ref().filter(|p| p.roots.is_none())
+        && let Some(roots) = draw_branch_picker(&pending.source)
+        && !state.menu_on
+    {
+        input::pick_branch(state, roots);
+    }
+    if let Some(message) = &state.message {
+        draw_text(message, 20.0, screen_height() - 20.0, 24.0, LIGHTGRAY);
+    }
+    if state.show_achievements {
+        draw_achievements(state);
+    }
+    draw_unlock_popups(state);
     if state.menu_on {
         // draw menu items (load, save, quit) and blur background
         draw_menu_overlay(state);
     }
-    if let Some(tree) = &state.skill_tree {
-        draw_skill_tree(tree);
+    if let Some(form) = &state.template_form {
+        draw_template_form(form);
+    }
+    if let Some(form) = &state.value_form {
+        draw_value_form(form);
     }
 }
+
+/// Lets the user pick a file and attaches it to `node`. Returns the status message.
+fn attach_file(state: &mut AppState, node: NodeId) -> Option<String> {
+    let path = FileDialog::new().set_title("Attach file").pick_file()?;
+    let resource = Resource::file(&path, state.file.as_deref().map(Path::new));
+    let name = resource.display_name();
+    let index = state.tree_mut().and_then(|t| t.node(node)).map_or(0, |n| n.resources.len());
+    match state.execute(Command::AddResource { node, index, resource }) {
+        Ok(()) if state.file.is_none() => Some(format!("Attached {}; its path becomes relative once the tree is saved", name)),
+        Ok(()) => Some(format!("Attached {}", name)),
+        Err(e) => Some(format!("Can't attach {}: {}", name, e)),
+    }
+}
+
+/// Turns a click into the undoable command it stands for.
+fn task_command(tree: &GTree, node: NodeId, click: TaskClick, today: NaiveDate) -> Result<Command, CommandError> {
+    let Some(kind) = tree.node(node).and_then(|n| n.tasks.get(click.task())).map(|t| t.kind.clone()) else {
+        return Err(CommandError::UnknownNode(node));
+    };
+    let command = match (click, kind) {
+        (TaskClick::Toggle(task), TaskKind::Habit(h)) => Command::SetHabitDone { node, task, day: today, done: !h.done_on(today) },
+        (TaskClick::Toggle(task), _) => Command::ToggleTask { node, task },
+        (TaskClick::Step(task, delta), TaskKind::Measure(m)) => Command::SetTaskValue { node, task, value: m.current + delta },
+        (TaskClick::Step(task, _) | TaskClick::Enter(task), _) => {
+            return Err(TaskError::WrongKind { node, index: task }.into());
+        }
+    };
+    Ok(command)
+}
+
 fn is_in_rect(mouse: Vec2, x: f32, y: f32, w: f32, h: f32) -> bool {
     mouse.x >= x && mouse.x <= x + w && mouse.y >= y && mouse.y <= y + h
 }
@@ -51,77 +174,554 @@ fn draw_menu_overlay(state: &mut AppState) {
         Color::new(0.0, 0.0, 0.0, 0.5),
     );
 
-    let menu_width = 300.0;
-    let menu_height = 200.0;
+    let menu_width = 400.0;
+    let menu_height = 450.0;
     let x = (screen_width() - menu_width) / 2.0;
     let y = (screen_height() - menu_height) / 2.0;
 
     draw_rectangle(x, y, menu_width, menu_height, Color::new(0.5, 0.5, 0.5, 0.9));
 
-    let items = [("Load", 50.0), ("Save", 100.0), ("Quit", 150.0)];
+    let items: [(&str, f32, &mut bool); 8] = [
+        ("Load", 50.0, &mut state.load),
+        ("New from template", 100.0, &mut state.new_from_template),
+        ("Import branch from file...", 150.0, &mut state.import_branch),
+        ("Open workspace...", 200.0, &mut state.open_workspace),
+        ("Add tree to workspace...", 250.0, &mut state.add_to_workspace),
+        ("Achievements", 300.0, &mut state.show_achievements),
+        ("Save", 350.0, &mut state.save),
+        ("Quit", 400.0, &mut state.quit),
+    ];
 
     let mouse: Vec2 = mouse_position().into();
 
-    for (label, offset_y) in items {
+    for (label, offset_y, flag) in items {
         let tx = x + 20.0;
         let ty = y + offset_y;
-        let tw = 100.0;
+        let tw = measure_text(label, None, 30, 1.0).width;
         let th = 35.0;
-        if is_in_rect(mouse, tx, ty - 25.0, tw, th) {
+        let hovered = is_in_rect(mouse, tx, ty - 25.0, tw, th);
+        if hovered {
             draw_rectangle(tx - 5.0, ty - 30.0, tw + 10.0, th, DARKGRAY);
+            if is_mouse_button_pressed(MouseButton::Left) {
+                *flag = true;
+            }
         }
         draw_text(label, tx, ty, 30.0, WHITE);
     }
+}
+
+/// Lists the nodes of a tree being imported, each standing for its branch, below an entry for
+/// the whole tree. Returns the roots of the branch clicked this frame.
+fn draw_branch_picker(source: &GTree) -> Option<Vec<NodeId>> {
+    draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.6));
+    let width = 520.0;
+    let row = 28.0;
+    let height = (80.0 + (source.nodes.len() + 1) as f32 * row).min(screen_height() - 40.0);
+    let x = (screen_width() - width) / 2.0;
+    let y = (screen_height() - height) / 2.0;
+    draw_rectangle(x, y, width, height, Color::new(0.15, 0.15, 0.2, 0.95));
+    draw_text(&format!("Import from \"{}\"", source.title), x + 20.0, y + 36.0, 26.0, WHITE);
+
+    let mouse: Vec2 = mouse_position().into();
+    let mut clicked = None;
+    let whole = (format!("Everything ({} nodes)", source.nodes.len()), source.roots());
+    let branches = source.nodes.iter().map(|n| (format!("{} ({} nodes)", n.title, source.subtree(n.id).len()), vec![n.id]));
+    let mut row_y = y + 60.0;
+    for (label, roots) in std::iter::once(whole).chain(branches) {
+        if row_y + row > y + height {
+            break;
+        }
+        let hovered = is_in_rect(mouse, x + 10.0, row_y, width - 20.0, row);
+        if hovered {
+            draw_rectangle(x + 10.0, row_y, width - 20.0, row, Color::new(1.0, 1.0, 1.0, 0.1));
+            if is_mouse_button_pressed(MouseButton::Left) {
+                clicked = Some(roots);
+            }
+        }
+        draw_text(&label, x + 20.0, row_y + 20.0, 20.0, WHITE);
+        row_y += row;
+    }
+    clicked
+}
+
+/// Every achievement, lit with its unlock date once earned. Clicking anywhere closes it.
+fn draw_achievements(state: &mut AppState) {
+    draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.7));
+    let width = 560.0;
+    let row = 56.0;
+    let height = 80.0 + ACHIEVEMENTS.len() as f32 * row;
+    let x = (screen_width() - width) / 2.0;
+    let y = (screen_height() - height) / 2.0;
+    draw_rectangle(x, y, width, height, Color::new(0.15, 0.15, 0.2, 0.95));
+    draw_text("Achievements", x + 20.0, y + 40.0, 32.0, WHITE);
+
+    let tree = app::current_tree(&mut state.skill_tree, &mut state.workspace, state.current_tree);
+    let mut row_y = y + 70.0;
+    for achievement in ACHIEVEMENTS {
+        let unlocked = tree.as_ref().and_then(|t| t.unlocked_at(achievement.id));
+        let (badge, text) = match unlocked {
+            Some(_) => (GOLD, WHITE),
+            None => (DARKGRAY, GRAY),
+        };
+        draw_circle(x + 36.0, row_y + 22.0, 14.0, badge);
+        draw_text(achievement.name, x + 64.0, row_y + 18.0, 24.0, text);
+        draw_text(achievement.description, x + 64.0, row_y + 40.0, 18.0, text);
+        let when = match unlocked {
+            Some(at) => at.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string(),
+            None => "Locked".to_string(),
+        };
+        let when_w = measure_text(&when, None, 18, 1.0).width;
+        draw_text(&when, x + width - when_w - 20.0, row_y + 18.0, 18.0, text);
+        row_y += row;
+    }
+    if is_mouse_button_pressed(MouseButton::Left) && !state.menu_on {
+        state.show_achievements = false;
+    }
+}
+
+/// How long an unlock pop-up stays up, in seconds.
+const UNLOCK_POPUP_SECS: f64 = 4.0;
+
+/// A banner at the top of the screen for each achievement unlocked in the last few seconds.
+fn draw_unlock_popups(state: &mut AppState) {
+    let now = get_time();
+    state.unlocks.retain(|(_, shown)| now - shown < UNLOCK_POPUP_SECS);
+    let width = 360.0;
+    let x = (screen_width() - width) / 2.0;
+    for (i, (achievement, shown)) in state.unlocks.iter().enumerate() {
+        // Fade out over the last second.
+        let alpha = ((UNLOCK_POPUP_SECS - (now - shown)) as f32).min(1.0);
+        let y = 20.0 + i as f32 * 76.0;
+        draw_rectangle(x, y, width, 66.0, Color::new(0.1, 0.1, 0.15, 0.9 * alpha));
+        draw_rectangle_lines(x, y, width, 66.0, 2.0, Color::new(GOLD.r, GOLD.g, GOLD.b, alpha));
+        draw_text("Achievement unlocked!", x + 16.0, y + 24.0, 20.0, Color::new(GOLD.r, GOLD.g, GOLD.b, alpha));
+        draw_text(achievement.name, x + 16.0, y + 52.0, 26.0, Color::new(1.0, 1.0, 1.0, alpha));
+    }
+}
+
+/// The question the template form is asking, with what has been typed so far.
+fn draw_template_form(form: &TemplateForm) {
+    let Some((question, default)) = form.question() else { return };
+    draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.5));
+
+    let width = 520.0;
+    let height = 170.0;
+    let x = (screen_width() - width) / 2.0;
+    let y = (screen_height() - height) / 2.0;
+    draw_rectangle(x, y, width, height, Color::new(0.5, 0.5, 0.5, 0.9));
+
+    let total = form.template.params.len() + form.template.optional.len();
+    draw_text(&format!("{} ({}/{})", form.template.name, form.step + 1, total), x + 20.0, y + 35.0, 26.0, WHITE);
+    draw_text(&question, x + 20.0, y + 70.0, 22.0, WHITE);
 
-    if state.menu_on && is_mouse_button_pressed(MouseButton::Left) {
-        if is_in_rect(mouse, x + 20.0, y + 25.0, 100.0, 40.0) {
-            state.load = true;
-        } else if is_in_rect(mouse, x + 20.0, y + 75.0, 100.0, 40.0) {
-            state.save = true;
-        } else if is_in_rect(mouse, x + 20.0, y + 125.0, 100.0, 40.0) {
-            state.quit = true;
+    // Text field; the default shows greyed out until something is typed.
+    draw_rectangle(x + 20.0, y + 85.0, width - 40.0, 32.0, Color::new(0.1, 0.1, 0.1, 1.0));
+    if form.input.is_empty() {
+        draw_text(&default, x + 28.0, y + 108.0, 22.0, GRAY);
+    } else {
+        draw_text(&format!("{}_", form.input), x + 28.0, y + 108.0, 22.0, WHITE);
+    }
+    draw_text("Enter to continue, Esc to cancel", x + 20.0, y + 150.0, 18.0, LIGHTGRAY);
+}
+/// The reading being typed for a measured task.
+fn draw_value_form(form: &ValueForm) {
+    draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.5));
+
+    let width = 520.0;
+    let height = 135.0;
+    let x = (screen_width() - width) / 2.0;
+    let y = (screen_height() - height) / 2.0;
+    draw_rectangle(x, y, width, height, Color::new(0.5, 0.5, 0.5, 0.9));
+    draw_text(&form.prompt, x + 20.0, y + 35.0, 22.0, WHITE);
+
+    // Text field; the current reading shows greyed out until something is typed.
+    draw_rectangle(x + 20.0, y + 50.0, width - 40.0, 32.0, Color::new(0.1, 0.1, 0.1, 1.0));
+    if form.input.is_empty() {
+        draw_text(&form.current.to_string(), x + 28.0, y + 73.0, 22.0, GRAY);
+    } else {
+        draw_text(&format!("{}_", form.input), x + 28.0, y + 73.0, 22.0, WHITE);
+    }
+    draw_text("Enter to set, Esc to cancel", x + 20.0, y + 115.0, 18.0, LIGHTGRAY);
+}
+
+/// What the user did to a task line this frame.
+#[derive(Clone, Copy)]
+pub enum TaskClick {
+    /// Clicked a checkbox task.
+    Toggle(usize),
+    /// Clicked the -/+ buttons of a measured task; holds the amount to add.
+    Step(usize, f32),
+    /// Clicked the "=" button of a measured task to type in a reading.
+    Enter(usize),
+}
+
+/// What the user did to a node card this frame.
+#[derive(Clone)]
+pub enum NodeClick {
+    Task(TaskClick),
+    /// Clicked a link in the description; holds its url.
+    OpenLink(String),
+    /// Clicked one of the node's resources.
+    OpenResource(usize),
+    /// Clicked "Attach file", only shown in edit mode.
+    AttachFile,
+    /// Clicked anywhere on the card while choosing where to import a branch.
+    Pick,
+}
+
+impl TaskClick {
+    fn task(self) -> usize {
+        match self {
+            TaskClick::Toggle(task) | TaskClick::Step(task, _) | TaskClick::Enter(task) => task,
         }
     }
 }
-pub fn draw_skill_tree(tree: &GTree) {
+
+/// Lists the habits that still want doing today, as `(node, task)`, in the top right corner.
+/// Clicking one marks it done for today.
+fn draw_due_today(tree: &GTree, due: &[(NodeId, usize)], today: NaiveDate) -> Option<(NodeId, TaskClick)> {
+    if due.is_empty() {
+        return None;
+    }
+    let width = 320.0;
+    let x = screen_width() - width - 20.0;
+    let y = 20.0;
+    let height = 40.0 + 22.0 * due.len() as f32;
+    draw_rectangle(x, y, width, height, Color::new(0.12, 0.12, 0.15, 0.85));
+    draw_text(&format!("Due today ({})", today.format("%a %d %b")), x + 10.0, y + 26.0, 22.0, SKYBLUE);
+
+    let mouse: Vec2 = mouse_position().into();
+    let mut clicked = None;
+    let mut line_y = y + 52.0;
+    for &(node_id, task) in due {
+        let Some(node) = tree.node(node_id) else { continue };
+        let text = format!("[ ] {}: {}", node.title, node.tasks[task].content);
+        if is_in_rect(mouse, x, line_y - 16.0, width, 22.0) {
+            draw_rectangle(x, line_y - 16.0, width, 22.0, Color::new(1.0, 1.0, 1.0, 0.1));
+            if is_mouse_button_pressed(MouseButton::Left) {
+                clicked = Some((node_id, TaskClick::Toggle(task)));
+            }
+        }
+        draw_text(&text, x + 10.0, line_y, 18.0, WHITE);
+        line_y += 22.0;
+    }
+    clicked
+}
+
+/// One tab per workspace tree plus the dashboard, above the tag filter.
+/// Returns the tab clicked this frame: `Some(None)` for the dashboard.
+fn draw_workspace_tabs(workspace: &Workspace, current: Option<TreeId>) -> Option<Option<TreeId>> {
+    let mouse: Vec2 = mouse_position().into();
+    let mut clicked = None;
+    let y = screen_height() - 110.0;
+    let mut x = 20.0;
+    let tabs = std::iter::once((None, "Dashboard".to_string()))
+        .chain(workspace.members.iter().map(|m| (Some(m.id), m.tree.title.clone())));
+    for (id, label) in tabs {
+        let w = measure_text(&label, None, 20, 1.0).width + 20.0;
+        let hovered = is_in_rect(mouse, x, y, w, 28.0);
+        let background = match (id == current, hovered) {
+            (true, _) => Color::new(0.2, 0.5, 0.8, 1.0),
+            (false, true) => GRAY,
+            (false, false) => DARKGRAY,
+        };
+        draw_rectangle(x, y, w, 28.0, background);
+        draw_text(&label, x + 10.0, y + 20.0, 20.0, WHITE);
+        if hovered && is_mouse_button_pressed(MouseButton::Left) {
+            clicked = Some(id);
+        }
+        x += w + 6.0;
+    }
+    clicked
+}
+
+/// Overall progress and one row per tree, with what each tree waits on in other trees.
+/// Returns the tree whose row was clicked this frame.
+fn draw_dashboard(workspace: &Workspace) -> Option<Option<TreeId>> {
+    let x = 80.0;
+    let width = screen_width() - 160.0;
+    let mut y = 40.0;
+    draw_text(&workspace.name, screen_width() * 0.5 - measure_text(&workspace.name, None, 40, 1.0).width / 2.0, y, 40.0, WHITE);
+    y += 40.0;
+    let overall = workspace.progress();
+    draw_text(&format!("Overall {:.0}%", overall * 100.0), x, y, 24.0, LIGHTGRAY);
+    draw_rectangle(x + 160.0, y - 14.0, width - 160.0, 12.0, BLACK);
+    draw_rectangle(x + 160.0, y - 14.0, (width - 160.0) * overall, 12.0, GREEN);
+    y += 30.0;
+
+    let mouse: Vec2 = mouse_position().into();
+    let mut clicked = None;
+    for summary in workspace.summaries() {
+        let height = 60.0 + 20.0 * summary.waiting_on.len() as f32;
+        let hovered = is_in_rect(mouse, x, y, width, height);
+        draw_rectangle(x, y, width, height, if hovered { Color::new(0.25, 0.25, 0.3, 1.0) } else { Color::new(0.15, 0.15, 0.2, 1.0) });
+        draw_text(&summary.title, x + 10.0, y + 26.0, 26.0, WHITE);
+        let counts = format!("{}/{} complete, {:.0}%", summary.complete, summary.nodes, summary.progress * 100.0);
+        let counts_w = measure_text(&counts, None, 20, 1.0).width;
+        draw_text(&counts, x + width - counts_w - 10.0, y + 26.0, 20.0, LIGHTGRAY);
+        draw_rectangle(x + 10.0, y + 38.0, width - 20.0, 8.0, BLACK);
+        draw_rectangle(x + 10.0, y + 38.0, (width - 20.0) * summary.progress, 8.0, GREEN);
+        let mut line_y = y + 64.0;
+        for link in &summary.waiting_on {
+            let name = |r: NodeRef| {
+                let tree = workspace.tree(r.tree).map_or("?", |t| t.title.as_str());
+                let node = workspace.node(r).map_or("?", |n| n.title.as_str());
+                format!("{} ({})", node, tree)
+            };
+            draw_text(&format!("{} waits on {}", name(link.to), name(link.from)), x + 20.0, line_y, 18.0, ORANGE);
+            line_y += 20.0;
+        }
+        if hovered && is_mouse_button_pressed(MouseButton::Left) {
+            clicked = Some(Some(summary.id));
+        }
+        y += height + 16.0;
+    }
+    clicked
+}
+
+/// Tag chips along the bottom edge, highlighted while selected in `filter`.
+/// Returns the tag clicked this frame.
+fn draw_tag_filter(tree: &GTree, filter: &TagFilter) -> Option<String> {
+    let tags = tree.all_tags();
+    if tags.is_empty() {
+        return None;
+    }
+    let mouse: Vec2 = mouse_position().into();
+    let mut clicked = None;
+    let y = screen_height() - 70.0;
+    let mut x = 20.0;
+    draw_text("Filter:", x, y + 17.0, 20.0, LIGHTGRAY);
+    x += 70.0;
+    for tag in tags {
+        let w = measure_text(&tag, None, 18, 1.0).width + 16.0;
+        let selected = filter.tags.contains(&tag);
+        let hovered = is_in_rect(mouse, x, y, w, 24.0);
+        let background = match (selected, hovered) {
+            (true, _) => Color::new(0.2, 0.5, 0.8, 1.0),
+            (false, true) => GRAY,
+            (false, false) => DARKGRAY,
+        };
+        draw_rectangle(x, y, w, 24.0, background);
+        draw_text(&tag, x + 8.0, y + 17.0, 18.0, WHITE);
+        if hovered && is_mouse_button_pressed(MouseButton::Left) {
+            clicked = Some(tag);
+        }
+        x += w + 8.0;
+    }
+    clicked
+}
+
+/// Draws the tree as a list of node cards, dimming the ones `filter` doesn't match.
+/// While `picking`, a click on a card picks the node instead of reaching its tasks.
+/// Returns the node and task a click landed on this frame.
+pub fn draw_skill_tree(
+    tree: &GTree,
+    states: &HashMap<NodeId, NodeState>,
+    filter: &TagFilter,
+    mode: Mode,
+    picking: bool,
+) -> Option<(NodeId, NodeClick)> {
     let screen_w = screen_width();
     let mut y = 40.0;
+    let overdue: HashSet<NodeId> = tree.overdue(habit::today()).iter().map(|d| d.item.node()).collect();
+    let mut clicked = None;
 
     // Draw tree title
     draw_text(&tree.title, screen_w * 0.5 - measure_text(&tree.title, None, 40, 1.0).width / 2.0, y, 40.0, WHITE);
     y += 60.0;
 
     for node in &tree.nodes {
-        draw_node(node, y);
-        y += 180.0; // space between nodes
+        let state = states.get(&node.id).copied().unwrap_or(NodeState::Locked);
+        let (height, task) = draw_node(node, state, overdue.contains(&node.id), mode, y);
+        if !filter.matches(node) {
+            draw_rectangle(80.0, y, screen_w - 160.0, height, Color::new(0.0, 0.0, 0.0, 0.6));
+        }
+        if picking {
+            if is_in_rect(mouse_position().into(), 80.0, y, screen_w - 160.0, height) {
+                draw_rectangle_lines(80.0, y, screen_w - 160.0, height, 4.0, SKYBLUE);
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    clicked = Some((node.id, NodeClick::Pick));
+                }
+            }
+        } else if let Some(task) = task {
+            clicked = Some((node.id, task));
+        }
+        y += height + 30.0; // space between nodes
     }
+    clicked
+}
+
+fn due_suffix(task: &Task) -> String {
+    match task.due {
+        Some(date) => format!("  (due {})", date),
+        None => String::new(),
+    }
+}
+
+fn node_color(color: Rgb) -> Color {
+    Color::from_rgba(color.0, color.1, color.2, 255)
 }
 
-fn draw_node(node: &GNode, y: f32) {
+fn state_colors(state: NodeState) -> (Color, &'static str) {
+    match state {
+        NodeState::Locked => (Color::new(0.15, 0.15, 0.15, 1.0), "Locked"),
+        NodeState::Available => (GRAY, "Available"),
+        NodeState::InProgress => (Color::new(0.2, 0.3, 0.5, 1.0), "In progress"),
+        NodeState::Complete => (Color::new(0.45, 0.38, 0.1, 1.0), "Complete"),
+    }
+}
+
+/// Draws one node card and returns its height, plus a click on it this frame.
+fn draw_node(node: &GNode, state: NodeState, overdue: bool, mode: Mode, y: f32) -> (f32, Option<NodeClick>) {
     let x = 80.0;
     let width = screen_width() - 160.0;
-    let height = 150.0;
+    let description = layout_markdown(&node.description, width - 20.0, 20.0);
+    // Room the description takes beyond the single line the card always has.
+    let extra = (description.height - 20.0).max(0.0);
+    let attach_line = mode == Mode::Edit;
+    let lines = node.tasks.len() + node.goals.len() + node.resources.len() + attach_line as usize;
+    let height = 110.0 + extra + 20.0 * lines as f32;
+    let (background, state_label) = state_colors(state);
+    let text_color = if state == NodeState::Locked { GRAY } else { WHITE };
+
+    // Node background, outlined in red while something in it is overdue
+    draw_rectangle(x, y, width, height, background);
+    if overdue {
+        draw_rectangle_lines(x, y, width, height, 4.0, RED);
+    }
+    if let Some(color) = node.color {
+        draw_rectangle(x, y, 6.0, height, node_color(color));
+    }
 
-    // Node background
-    draw_rectangle(x, y, width, height, if node.is_lit { DARKGRAY } else { GRAY });
+    // Node title and state
+    draw_text(&node.title, x + 10.0, y + 30.0, 28.0, text_color);
+    let state_label = match node.target_date {
+        Some(date) => format!("{}  target {}", state_label, date),
+        None => state_label.to_string(),
+    };
+    let label_w = measure_text(&state_label, None, 20, 1.0).width;
+    draw_text(&state_label, x + width - label_w - 10.0, y + 30.0, 20.0, if overdue { RED } else { LIGHTGRAY });
 
-    // Node title
-    draw_text(&node.title, x + 10.0, y + 30.0, 28.0, WHITE);
+    // Category and tags after the title
+    let mut labels: Vec<String> = node.category.iter().cloned().collect();
+    labels.extend(node.tags.iter().map(|t| format!("#{}", t)));
+    if !labels.is_empty() {
+        let title_w = measure_text(&node.title, None, 28, 1.0).width;
+        draw_text(&labels.join("  "), x + 24.0 + title_w, y + 30.0, 18.0, SKYBLUE);
+    }
 
     // Description
-    draw_text(&node.description, x + 10.0, y + 60.0, 20.0, LIGHTGRAY);
+    let link = draw_markdown(&description, x + 10.0, y + 40.0, LIGHTGRAY);
 
     // Progress bar background
-    draw_rectangle(x + 10.0, y + 80.0, width - 20.0, 10.0, BLACK);
+    draw_rectangle(x + 10.0, y + 80.0 + extra, width - 20.0, 10.0, BLACK);
     // Progress bar fill
-    draw_rectangle(x + 10.0, y + 80.0, (width - 20.0) * (node.progress / 100.0), 10.0, GREEN);
+    draw_rectangle(x + 10.0, y + 80.0 + extra, (width - 20.0) * node.progress(), 10.0, GREEN);
 
     // Tasks
-    let mut task_y = y + 100.0;
-    for task in &node.tasks {
-        let checkbox = if task.checked { "[x]" } else { "[ ]" };
-        let task_text = format!("{} {}", checkbox, task.content);
-        draw_text(&task_text, x + 10.0, task_y, 18.0, WHITE);
-        task_y += 20.0;
-    }
-}
\ No newline at end of file
+    let mouse: Vec2 = mouse_position().into();
+    let mut clicked = None;
+    let mut line_y = y + 110.0 + extra;
+    let step = if is_key_down(KeyCode::LeftShift) { 10.0 } else { 1.0 };
+    for (i, task) in node.tasks.iter().enumerate() {
+        match &task.kind {
+            TaskKind::Check => {
+                let checkbox = if task.checked { "[x]" } else { "[ ]" };
+                let task_text = format!("{} {}{}", checkbox, task.content, due_suffix(task));
+                if is_in_rect(mouse, x + 10.0, line_y - 15.0, width - 20.0, 20.0) {
+                    draw_rectangle(x + 5.0, line_y - 15.0, width - 10.0, 20.0, Color::new(1.0, 1.0, 1.0, 0.1));
+                    if is_mouse_button_pressed(MouseButton::Left) {
+                        clicked = Some(TaskClick::Toggle(i));
+                    }
+                }
+                draw_text(&task_text, x + 10.0, line_y, 18.0, text_color);
+            }
+            TaskKind::Habit(h) => {
+                let today = habit::today();
+                let checkbox = if h.done_on(today) { "[x]" } else { "[ ]" };
+                let schedule = match h.schedule {
+                    Schedule::Daily => "daily".to_string(),
+                    Schedule::Weekdays => "weekdays".to_string(),
+                    Schedule::TimesPerWeek(times) => format!("{}x a week", times),
+                };
+                let task_text = format!(
+                    "{} {}  ({}, streak {}, best {}/{}){}",
+                    checkbox,
+                    task.content,
+                    schedule,
+                    h.current_streak(today),
+                    h.best_streak(),
+                    h.target_streak,
+                    due_suffix(task)
+                );
+                if is_in_rect(mouse, x + 10.0, line_y - 15.0, width - 20.0, 20.0) {
+                    draw_rectangle(x + 5.0, line_y - 15.0, width - 10.0, 20.0, Color::new(1.0, 1.0, 1.0, 0.1));
+                    if is_mouse_button_pressed(MouseButton::Left) {
+                        clicked = Some(TaskClick::Toggle(i));
+                    }
+                }
+                draw_text(&task_text, x + 10.0, line_y, 18.0, text_color);
+            }
+            TaskKind::Measure(m) => {
+                let checkbox = if m.is_reached() { "[x]" } else { "[~]" };
+                let task_text = format!(
+                    "{} {}  {} / {} {}{}",
+                    checkbox, task.content, m.current, m.target, m.unit, due_suffix(task)
+                );
+                draw_text(&task_text, x + 10.0, line_y, 18.0, text_color);
+                // =/-/+ buttons on the right edge: type a reading, or step it; hold shift for steps of 10.
+                let buttons = [
+                    ("=", TaskClick::Enter(i), x + width - 88.0),
+                    ("-", TaskClick::Step(i, -step), x + width - 60.0),
+                    ("+", TaskClick::Step(i, step), x + width - 32.0),
+                ];
+                for (label, click, bx) in buttons {
+                    let hovered = is_in_rect(mouse, bx, line_y - 15.0, 22.0, 18.0);
+                    draw_rectangle(bx, line_y - 15.0, 22.0, 18.0, if hovered { LIGHTGRAY } else { DARKGRAY });
+                    draw_text(label, bx + 7.0, line_y, 18.0, WHITE);
+                    if hovered && is_mouse_button_pressed(MouseButton::Left) {
+                        clicked = Some(click);
+                    }
+                }
+            }
+        }
+        line_y += 20.0;
+    }
+
+    // Goals
+    for goal in &node.goals {
+        let marker = if goal.done { "(*)" } else { "( )" };
+        let goal_text = match &goal.notes {
+            Some(notes) => format!("{} {} - {}", marker, goal.text, notes),
+            None => format!("{} {}", marker, goal.text),
+        };
+        draw_text(&goal_text, x + 10.0, line_y, 18.0, SKYBLUE);
+        line_y += 20.0;
+    }
+
+    // Resources, opened with the system's handler
+    let mut clicked = clicked.map(NodeClick::Task).or(link.map(NodeClick::OpenLink));
+    for (i, resource) in node.resources.iter().enumerate() {
+        let text = format!("[{}] {}", resource.kind.label(), resource.display_name());
+        let hovered = is_in_rect(mouse, x + 10.0, line_y - 15.0, width - 20.0, 20.0);
+        if hovered {
+            draw_rectangle(x + 5.0, line_y - 15.0, width - 10.0, 20.0, Color::new(1.0, 1.0, 1.0, 0.1));
+            if is_mouse_button_pressed(MouseButton::Left) {
+                clicked = Some(NodeClick::OpenResource(i));
+            }
+        }
+        draw_text(&text, x + 10.0, line_y, 18.0, Color::new(0.6, 0.8, 1.0, 1.0));
+        line_y += 20.0;
+    }
+    if attach_line {
+        if is_in_rect(mouse, x + 10.0, line_y - 15.0, 120.0, 20.0) {
+            draw_rectangle(x + 5.0, line_y - 15.0, 130.0, 20.0, Color::new(1.0, 1.0, 1.0, 0.1));
+            if is_mouse_button_pressed(MouseButton::Left) {
+                clicked = Some(NodeClick::AttachFile);
+            }
+        }
+        draw_text("+ Attach file", x + 10.0, line_y, 18.0, GRAY);
+    }
+
+    (height, clicked)
+}
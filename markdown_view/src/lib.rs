@@ -0,0 +1,57 @@
+//! Drawing Markdown descriptions with macroquad.
+//!
+//! Shared by grind_trees_desktop and the graph viewer in the repository root.
+
+use core::markdown::{self, Layout};
+use macroquad::prelude::*;
+
+/// Hands a url or file to the system's default handler.
+pub fn open_location(location: &str) {
+    println!("Opening {}", location);
+    if let Err(e) = open::that_detached(location) {
+        println!("Couldn't open {}: {}", location, e);
+    }
+}
+
+pub fn layout_markdown(text: &str, max_width: f32, font_size: f32) -> Layout {
+    markdown::layout(&markdown::parse(text), max_width, font_size, |text, style, size| {
+        // Bold text is drawn twice, one pixel apart.
+        measure_text(text, None, size as u16, 1.0).width + if style.bold { 1.0 } else { 0.0 }
+    })
+}
+
+/// Draws laid out Markdown with its top left corner at (x, y).
+/// Links are underlined; returns the url of a link clicked this frame.
+pub fn draw_markdown(layout: &Layout, x: f32, y: f32, color: Color) -> Option<String> {
+    let (mx, my) = mouse_position();
+    let mut clicked = None;
+    for line in &layout.lines {
+        let baseline = y + line.baseline;
+        for run in &line.runs {
+            let rx = x + run.x;
+            let w = measure_text(&run.text, None, run.size as u16, 1.0).width;
+            let run_color = match (&run.link, run.style.code, run.style.italic) {
+                (Some(_), _, _) => SKYBLUE,
+                (None, true, _) => ORANGE,
+                // The default font has no italic face, so italics get a softer colour instead.
+                (None, false, true) => Color::new(color.r * 0.8, color.g * 0.8, color.b, color.a),
+                (None, false, false) => color,
+            };
+            if run.style.code {
+                draw_rectangle(rx - 2.0, baseline - run.size * 0.75, w + 4.0, run.size, Color::new(0.0, 0.0, 0.0, 0.35));
+            }
+            draw_text(&run.text, rx, baseline, run.size, run_color);
+            if run.style.bold {
+                draw_text(&run.text, rx + 1.0, baseline, run.size, run_color);
+            }
+            if let Some(url) = &run.link {
+                draw_line(rx, baseline + 2.0, rx + w, baseline + 2.0, 1.0, run_color);
+                let top = baseline - run.size * 0.75;
+                if mx >= rx && mx <= rx + w && my >= top && my <= top + run.size && is_mouse_button_pressed(MouseButton::Left) {
+                    clicked = Some(url.clone());
+                }
+            }
+        }
+    }
+    clicked
+}
@@ -1,5 +1,8 @@
 use macroquad::prelude::*;
-use crate::gtree::{GNode, GTree, Task};
+use core::habit;
+use markdown_view::{draw_markdown, layout_markdown, open_location};
+use std::path::Path;
+use core::skill_tree::{Edge, EdgeKind, GNode, GTree, NodeId, NodeState, TaskKind};
 
 pub struct Camera{
     pub zoom: f32,
@@ -63,42 +66,46 @@ impl Camera {
     }
 }
 
-fn draw_connection(cam: &Camera, gtree: &GTree, node_idx: usize) {
-    let node = &gtree.nodes[node_idx];
-    if let Some(parent_idx) = node.parent {
-        if parent_idx >= 0 {
-            let parent_idx = parent_idx as usize;
-            if let Some(parent) = gtree.nodes.get(parent_idx) {
-                let pos_a = vec2(node.x, node.y) * cam.zoom + vec2(cam.offset.0, cam.offset.1);
-                let pos_b = vec2(parent.x, parent.y) * cam.zoom + vec2(cam.offset.0, cam.offset.1);
-                let color = if parent.is_lit { YELLOW } else { DARKGRAY };
-                draw_line(pos_a.x, pos_a.y, pos_b.x, pos_b.y, 4.0, color);
-            }
-        }
+fn draw_connection(cam: &Camera, gtree: &GTree, edge: &Edge, to_state: NodeState) {
+    if let (Some(from), Some(to)) = (gtree.node(edge.from), gtree.node(edge.to)) {
+        let pos_a = vec2(to.x, to.y) * cam.zoom + vec2(cam.offset.0, cam.offset.1);
+        let pos_b = vec2(from.x, from.y) * cam.zoom + vec2(cam.offset.0, cam.offset.1);
+        let color = match to_state {
+            NodeState::Locked => DARKGRAY,
+            _ => YELLOW,
+        };
+        let thickness = match edge.kind {
+            EdgeKind::Requires => 4.0,
+            EdgeKind::Recommends => 2.0,
+        };
+        draw_line(pos_a.x, pos_a.y, pos_b.x, pos_b.y, thickness, color);
     }
 }
 
-pub fn draw_tree(cam: &Camera, gtree: &GTree, selected_node: &mut Option<usize>) {
+pub fn draw_tree(cam: &Camera, gtree: &GTree, selected_node: &mut Option<NodeId>) {
+    let states = gtree.node_states();
+    let state_of = |id: NodeId| states.get(&id).copied().unwrap_or(NodeState::Locked);
     // First, draw all connections
-    for i in 0..gtree.nodes.len() {
-        draw_connection(cam, gtree, i);
+    for edge in &gtree.edges {
+        draw_connection(cam, gtree, edge, state_of(edge.to));
     }
     // Then, draw all nodes
     let mouse: Vec2 = mouse_position().into();
-    let mut hovered_node: Option<usize> = None;
+    let mut hovered_node: Option<&GNode> = None;
 
-    for (i, node) in gtree.nodes.iter().enumerate() {
+    let overdue: Vec<NodeId> = gtree.overdue(habit::today()).iter().map(|d| d.item.node()).collect();
+    for node in &gtree.nodes {
         if is_mouse_over_node(cam, node, mouse) {
-            hovered_node = Some(i);
+            hovered_node = Some(node);
             if is_mouse_button_pressed(MouseButton::Left) {
-                *selected_node = Some(i);
+                *selected_node = Some(node.id);
             }
         }
-        draw_node(cam, node);
+        draw_node(cam, node, state_of(node.id), overdue.contains(&node.id));
     }
 
-    if let Some(i) = hovered_node {
-        draw_node_hover_menu(cam, &gtree.nodes[i]);
+    if let Some(node) = hovered_node {
+        draw_node_hover_menu(cam, node);
     }
 }
 
@@ -106,14 +113,26 @@ lazy_static::lazy_static! {
     static ref NODE_TEXTURE: Texture2D = Texture2D::from_file_with_format(include_bytes!("../assets/circle.png"), None);
 }
 
-pub fn draw_node(cam: &Camera, node: &GNode){
+pub fn state_color(state: NodeState) -> Color {
+    match state {
+        NodeState::Locked => DARKGRAY,
+        NodeState::Available => WHITE,
+        NodeState::InProgress => SKYBLUE,
+        NodeState::Complete => GOLD,
+    }
+}
+
+pub fn draw_node(cam: &Camera, node: &GNode, state: NodeState, overdue: bool){
     let r = node.r * cam.zoom;
     let new_pos: Vec2 = vec2(node.x, node.y) * cam.zoom + vec2(cam.offset.0, cam.offset.1);
+    if overdue {
+        draw_circle_lines(new_pos.x, new_pos.y, r + 4.0 * cam.zoom, 3.0, RED);
+    }
     draw_texture_ex(
         &NODE_TEXTURE,
         new_pos.x - r,
         new_pos.y - r,
-        WHITE,
+        state_color(state),
         DrawTextureParams {
             dest_size: Some(Vec2::new(r * 2.0, r * 2.0)),
             ..Default::default()
@@ -135,11 +154,10 @@ pub fn draw_node_hover_menu(cam: &Camera, node: &GNode) {
     let desc_font_size = 18.0;
 
     // Word wrap the description
-    let wrapped = wrap_text(&node.description, menu_width - 2.0 * padding, desc_font_size);
+    let description = layout_markdown(&node.description, menu_width - 2.0 * padding, desc_font_size);
 
     // Calculate height based on lines
-    let desc_lines = wrapped.len() as f32;
-    let menu_height = 40.0 + desc_lines * desc_font_size + padding * 2.0;
+    let menu_height = 40.0 + description.height + padding * 2.0;
 
     let x = node_pos.x + 20.0;
     let y = node_pos.y - menu_height / 2.0;
@@ -147,16 +165,12 @@ pub fn draw_node_hover_menu(cam: &Camera, node: &GNode) {
     draw_rectangle(x, y, menu_width, menu_height, Color::new(0.18, 0.18, 0.22, 0.92));
     draw_text(&node.title, x + padding, y + padding + title_height, title_height, YELLOW);
 
-    let mut y_offset = y + padding + title_height + 20.0;
-    for line in wrapped {
-        draw_text(&line, x + padding, y_offset, desc_font_size, LIGHTGRAY);
-        y_offset += desc_font_size;
-    }
+    draw_markdown(&description, x + padding, y + padding + title_height + 4.0, LIGHTGRAY);
 }
 
 /// Draws a persistent left-side menu with all details of a GNode.
-/// Returns true if the close button was clicked.
-pub fn draw_gnode_detail_menu(node: &GNode) -> bool {
+/// Returns true if the close button was clicked. Resources open relative to `save_file`.
+pub fn draw_gnode_detail_menu(node: &GNode, save_file: &str) -> bool {
     let menu_width = 400.0;
     let menu_height = screen_height() - 80.0;
     let x = 30.0;
@@ -188,17 +202,20 @@ pub fn draw_gnode_detail_menu(node: &GNode) -> bool {
     // Description (wrapped)
     let desc_font_size = 20.0;
     let desc_y = y + 90.0;
-    let desc_lines = wrap_text(&node.description, menu_width - 2.0 * padding, desc_font_size);
-    let mut y_offset = desc_y;
-    for line in desc_lines {
-        draw_text(&line, x + padding, y_offset, desc_font_size, LIGHTGRAY);
-        y_offset += desc_font_size + 2.0;
+    let description = layout_markdown(&node.description, menu_width - 2.0 * padding, desc_font_size);
+    if let Some(url) = draw_markdown(&description, x + padding, desc_y - desc_font_size, LIGHTGRAY) {
+        open_location(&url);
     }
+    let y_offset = desc_y + description.height;
 
     // Progress
     let progress_y = y_offset + 16.0;
+    let target = match node.target_date {
+        Some(date) => format!(" (target {})", date),
+        None => String::new(),
+    };
     draw_text(
-        &format!("Progress: {:.0}%", node.progress * 100.0),
+        &format!("Progress: {:.0}%{}", node.progress() * 100.0, target),
         x + padding,
         progress_y,
         22.0,
@@ -209,48 +226,69 @@ pub fn draw_gnode_detail_menu(node: &GNode) -> bool {
     let tasks_y = progress_y + 36.0;
     draw_text("Tasks:", x + padding, tasks_y, 22.0, SKYBLUE);
     let mut task_y = tasks_y + 28.0;
-    if let Some(tasks) = &node.tasks {
-        for task in tasks {
-            let check = if task.checked { "[x]" } else { "[ ]" };
-            draw_text(&format!("{} {}", check, task.content), x + padding + 16.0, task_y, 20.0, WHITE);
-            task_y += 24.0;
-        }
-    } else {
+    if node.tasks.is_empty() {
         draw_text("No tasks.", x + padding + 16.0, task_y, 20.0, GRAY);
         task_y += 24.0;
     }
+    for task in &node.tasks {
+        let check = if task.checked { "[x]" } else { "[ ]" };
+        let text = match &task.kind {
+            TaskKind::Check => format!("{} {}", check, task.content),
+            TaskKind::Measure(m) => format!("{} {} ({} / {} {})", check, task.content, m.current, m.target, m.unit),
+            TaskKind::Habit(h) => format!(
+                "{} {} (streak {}, best {}/{})",
+                check,
+                task.content,
+                h.current_streak(habit::today()),
+                h.best_streak(),
+                h.target_streak
+            ),
+        };
+        let text = match task.due {
+            Some(date) => format!("{} (due {})", text, date),
+            None => text,
+        };
+        draw_text(&text, x + padding + 16.0, task_y, 20.0, WHITE);
+        task_y += 24.0;
+    }
+
+    // Goals
+    let goals_y = task_y + 16.0;
+    draw_text("Goals:", x + padding, goals_y, 22.0, SKYBLUE);
+    let mut goal_y = goals_y + 28.0;
+    if node.goals.is_empty() {
+        draw_text("No goals.", x + padding + 16.0, goal_y, 20.0, GRAY);
+        goal_y += 24.0;
+    }
+    for goal in &node.goals {
+        let check = if goal.done { "[x]" } else { "[ ]" };
+        draw_text(&format!("{} {}", check, goal.text), x + padding + 16.0, goal_y, 20.0, WHITE);
+        goal_y += 24.0;
+    }
+
+    // Resources
+    let mut resource_y = goal_y;
+    if !node.resources.is_empty() {
+        resource_y += 16.0;
+        draw_text("Resources:", x + padding, resource_y, 22.0, SKYBLUE);
+        resource_y += 28.0;
+    }
+    let (mx, my) = mouse_position();
+    for resource in &node.resources {
+        let text = format!("[{}] {}", resource.kind.label(), resource.display_name());
+        let hovered = mx >= x && mx <= x + menu_width && my >= resource_y - 18.0 && my <= resource_y + 6.0;
+        draw_text(&text, x + padding + 16.0, resource_y, 20.0, if hovered { SKYBLUE } else { WHITE });
+        if hovered && is_mouse_button_pressed(MouseButton::Left) {
+            open_location(&resource.location(Some(Path::new(save_file))));
+        }
+        resource_y += 24.0;
+    }
 
     // Lit status
-    let lit_y = task_y + 16.0;
-    let lit_text = if node.is_lit { "Lit: Yes" } else { "Lit: No" };
-    let lit_color = if node.is_lit { YELLOW } else { DARKGRAY };
+    let lit_y = resource_y + 16.0;
+    let lit_text = if node.is_lit() { "Lit: Yes" } else { "Lit: No" };
+    let lit_color = if node.is_lit() { YELLOW } else { DARKGRAY };
     draw_text(lit_text, x + padding, lit_y, 22.0, lit_color);
 
     close_clicked
 }
-
-/// Simple word wrap for a string, returns lines that fit within max_width for the given font size.
-fn wrap_text(text: &str, max_width: f32, font_size: f32) -> Vec<String> {
-    let mut lines = Vec::new();
-    let words: Vec<&str> = text.split_whitespace().collect();
-    let mut current = String::new();
-
-    for word in words {
-        let test = if current.is_empty() {
-            word.to_string()
-        } else {
-            format!("{} {}", current, word)
-        };
-        let width = measure_text(&test, None, font_size as u16, 1.0).width;
-        if width > max_width && !current.is_empty() {
-            lines.push(current.clone());
-            current = word.to_string();
-        } else {
-            current = test;
-        }
-    }
-    if !current.is_empty() {
-        lines.push(current);
-    }
-    lines
-}
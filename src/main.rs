@@ -2,32 +2,68 @@ mod gtree;
 mod camera;
 mod side_menu;
 
-use gtree::{load_gtree_from_file, handle_save_shortcuts};
+use core::forecast::Forecast;
+use core::habit;
+use core::skill_tree::{load_tree_from_file, GTree, NodeId};
+use gtree::handle_save_shortcuts;
 use camera::{Camera, draw_tree, draw_gnode_detail_menu};
 use macroquad::prelude::*;
 
 #[macroquad::main("Grind Trees")]
 async fn main() {
-    let mut gtree = load_gtree_from_file("new_state.json").expect("Failed to load");
+    let mut load_error = None;
+    let mut gtree = match load_tree_from_file("new_state.json") {
+        Ok(tree) => {
+            for diagnostic in tree.validate() {
+                println!("{}", diagnostic);
+            }
+            tree
+        }
+        Err(e) => {
+            println!("Failed to load new_state.json: {}", e);
+            load_error = Some(format!("Couldn't load new_state.json: {}", e));
+            GTree::new("Grind Trees")
+        }
+    };
     let mut cam = Camera::new();
     let mut show_side_menu = false;
-    let mut selected_node: Option<usize> = None;
+    let mut selected_node: Option<NodeId> = None;
     let mut current_file = String::from("new_state.json");
 
     loop {
         clear_background(BLACK);
         draw_text("Grind Trees", 20.0, 40.0, 30.0, DARKGRAY);
+        if let Some(message) = &load_error {
+            draw_text(message, 20.0, screen_height() - 20.0, 22.0, RED);
+        }
         cam.update();
 
         draw_tree(&cam, &gtree, &mut selected_node);
-        show_side_menu = side_menu::handle_side_menu(show_side_menu, gtree.progress);
-        if let Some(i) = selected_node {
-            let close = draw_gnode_detail_menu(&gtree.nodes[i]);
-            if close {
-                selected_node = None;
+        let today = habit::today();
+        let branches: Vec<(String, f32, Option<Forecast>)> = gtree
+            .branches()
+            .into_iter()
+            .filter_map(|id| {
+                let title = gtree.node(id)?.title.clone();
+                Some((title, gtree.subtree_progress(id), gtree.branch_forecast(id, today)))
+            })
+            .collect();
+        let selected = selected_node
+            .and_then(|id| Some((gtree.node(id)?.title.as_str(), gtree.node_forecast(id, today))));
+        show_side_menu =
+            side_menu::handle_side_menu(show_side_menu, gtree.progress(), gtree.forecast(today), &branches, selected);
+        if let Some(id) = selected_node {
+            // The selection may outlive its node, so look it up instead of indexing.
+            match gtree.node(id) {
+                Some(node) => {
+                    if draw_gnode_detail_menu(node, &current_file) {
+                        selected_node = None;
+                    }
+                }
+                None => selected_node = None,
             }
         }
-        handle_save_shortcuts(&gtree, &mut current_file);
+        handle_save_shortcuts(&mut gtree, &mut current_file);
         next_frame().await;
     }
 }
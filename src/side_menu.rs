@@ -1,3 +1,4 @@
+use core::forecast::Forecast;
 use macroquad::prelude::*;
 
 pub fn lerp_color(a: Color, b: Color, t: f32) -> Color {
@@ -9,9 +10,28 @@ pub fn lerp_color(a: Color, b: Color, t: f32) -> Color {
     )
 }
 
-pub fn draw_side_menu(progress: f32) {
-    let menu_width = 300.0;
-    let menu_height = 120.0;
+/// Hours left and the forecast finish date, for a line under a progress bar.
+fn forecast_text(forecast: Option<Forecast>) -> String {
+    match forecast {
+        None => "No estimate".to_string(),
+        Some(f) if f.is_done() => "Done".to_string(),
+        Some(Forecast { remaining, finish: Some(date), .. }) => format!("{:.1}h left, done by {}", remaining, date),
+        Some(Forecast { remaining, .. }) => format!("{:.1}h left, no recent progress", remaining),
+    }
+}
+
+/// Draws the total progress bar with one smaller bar per branch below it, each with its
+/// forecast, and the forecast for the selected node at the bottom.
+pub fn draw_side_menu(
+    progress: f32,
+    forecast: Option<Forecast>,
+    branches: &[(String, f32, Option<Forecast>)],
+    selected: Option<(&str, Option<Forecast>)>,
+) {
+    let menu_width = 340.0;
+    let branch_row = 62.0;
+    let selected_height = if selected.is_some() { 56.0 } else { 0.0 };
+    let menu_height = 150.0 + branches.len() as f32 * branch_row + selected_height;
     let margin = 40.0;
     let x = screen_width() - menu_width - margin;
     let y = margin;
@@ -41,16 +61,52 @@ pub fn draw_side_menu(progress: f32) {
         32.0,
         WHITE,
     );
+
+    draw_text(&forecast_text(forecast), bar_x, bar_y + bar_h + 22.0, 20.0, LIGHTGRAY);
+
+    // Branch bars
+    let mut row_y = bar_y + bar_h + 56.0;
+    for (title, branch_progress, branch_forecast) in branches {
+        let branch_progress = branch_progress.clamp(0.0, 1.0);
+        draw_text(
+            &format!("{}: {:.0}%", title, branch_progress * 100.0),
+            bar_x,
+            row_y,
+            20.0,
+            LIGHTGRAY,
+        );
+        draw_rectangle(bar_x, row_y + 6.0, bar_w, 12.0, DARKGRAY);
+        draw_rectangle(
+            bar_x,
+            row_y + 6.0,
+            bar_w * branch_progress,
+            12.0,
+            lerp_color(RED, GREEN, branch_progress),
+        );
+        draw_text(&forecast_text(*branch_forecast), bar_x, row_y + 36.0, 16.0, GRAY);
+        row_y += branch_row;
+    }
+
+    if let Some((title, node_forecast)) = selected {
+        draw_text(&format!("Selected: {}", title), bar_x, row_y, 20.0, WHITE);
+        draw_text(&forecast_text(node_forecast), bar_x, row_y + 22.0, 16.0, GRAY);
+    }
 }
 
 /// Handles toggling and drawing the side menu.
 /// Returns the new state of show_side_menu.
-pub fn handle_side_menu(mut show_side_menu: bool, progress: f32) -> bool {
+pub fn handle_side_menu(
+    mut show_side_menu: bool,
+    progress: f32,
+    forecast: Option<Forecast>,
+    branches: &[(String, f32, Option<Forecast>)],
+    selected: Option<(&str, Option<Forecast>)>,
+) -> bool {
     if is_key_down(KeyCode::LeftControl) && is_key_pressed(KeyCode::P) {
         show_side_menu = !show_side_menu;
     }
     if show_side_menu {
-        draw_side_menu(progress);
+        draw_side_menu(progress, forecast, branches, selected);
     }
     show_side_menu
 }
\ No newline at end of file